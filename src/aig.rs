@@ -0,0 +1,450 @@
+use crate::boolean_expression::*;
+use crate::token::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::Not;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// An edge of an and-inverter graph, which is the output of a node or its negation.
+/// It is stored as 2 * node + 1 if it is complemented, the same way as the literals of the AIGER format,
+/// so node 0 is the constant false.
+pub struct AigLiteral(u32);
+
+impl AigLiteral {
+    pub const FALSE: AigLiteral = AigLiteral(0);
+    pub const TRUE: AigLiteral = AigLiteral(1);
+
+    #[inline]
+    fn new(node: usize, complemented: bool) -> Self {
+        AigLiteral((node as u32) << 1 | complemented as u32)
+    }
+
+    #[inline]
+    pub fn node(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    #[inline]
+    pub fn is_complemented(self) -> bool {
+        self.0 & 1 == 1
+    }
+}
+
+impl Not for AigLiteral {
+    type Output = AigLiteral;
+
+    #[inline]
+    fn not(self) -> AigLiteral {
+        AigLiteral(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AigNode {
+    Constant,
+    Input(u32),
+    And(AigLiteral, AigLiteral),
+}
+
+/// An and-inverter graph (AIG), where every function is built from two-input AND nodes and complemented
+/// edges. This is the form that logic synthesis tools work with and exchange (see to_aiger).
+/// The nodes are structurally hashed, so an AND of the same two edges is created once, and every new AND
+/// is simplified first: constants are propagated and a few rules that look one level into the operands
+/// remove redundant nodes (i.e (A && B) && !A is false and !(A && B) && A is A && !B).
+/// Node 0 is the constant false, the next nodes are the inputs in the order of the variable ids and
+/// every AND node comes after its operands.
+pub struct Aig {
+    nodes: Vec<AigNode>,
+    // The number of AND nodes on the longest path from an input to every node
+    levels: Vec<usize>,
+    strash: HashMap<(AigLiteral, AigLiteral), AigLiteral>,
+    number_of_vars: usize,
+}
+
+impl Aig {
+    /// Creates a graph with the inputs of the given number of variables.
+    pub fn new(number_of_vars: usize) -> Self {
+        let mut nodes = vec![AigNode::Constant];
+        nodes.extend((0..number_of_vars as u32).map(AigNode::Input));
+        Self {
+            levels: vec![0; nodes.len()],
+            nodes,
+            strash: HashMap::new(),
+            number_of_vars,
+        }
+    }
+
+    /// Creates a graph over the variables of an expression and adds the expression to it.
+    pub fn from_expression(bexp: &BooleanExpression) -> (Self, AigLiteral) {
+        let mut aig = Self::new(bexp.variables().len());
+        let root = aig.add_expression(bexp);
+        (aig, root)
+    }
+
+    /// Adds an expression whose variable ids are inputs of the graph.
+    /// OR and XOR are expressed with ANDs, XOR needing three of them.
+    pub fn add_expression(&mut self, bexp: &BooleanExpression) -> AigLiteral {
+        bexp.fold(|node: Node<AigLiteral>| match node {
+            Node::Variable(id) => self.input(id),
+            Node::Constant(0) => AigLiteral::FALSE,
+            Node::Constant(_) => AigLiteral::TRUE,
+            Node::Unary(_, operand) => !operand,
+            Node::Binary(Token::AND, lhs, rhs) => self.and(lhs, rhs),
+            Node::Binary(Token::OR, lhs, rhs) => self.or(lhs, rhs),
+            Node::Binary(_, lhs, rhs) => self.xor(lhs, rhs),
+        })
+    }
+
+    #[inline]
+    pub fn number_of_vars(&self) -> usize {
+        self.number_of_vars
+    }
+
+    #[inline]
+    pub fn input(&self, id: u32) -> AigLiteral {
+        AigLiteral::new(id as usize + 1, false)
+    }
+
+    pub fn and(&mut self, lhs: AigLiteral, rhs: AigLiteral) -> AigLiteral {
+        let (lhs, rhs) = (lhs.min(rhs), lhs.max(rhs));
+        // The constants are the smallest literals
+        if lhs == AigLiteral::FALSE || lhs == !rhs {
+            return AigLiteral::FALSE;
+        }
+        if lhs == AigLiteral::TRUE || lhs == rhs {
+            return rhs;
+        }
+        if let Some(res) = self.rewrite(lhs, rhs).or_else(|| self.rewrite(rhs, lhs)) {
+            return res;
+        }
+        if let Some(res) = self.strash.get(&(lhs, rhs)) {
+            return *res;
+        }
+        self.nodes.push(AigNode::And(lhs, rhs));
+        self.levels
+            .push(1 + self.levels[lhs.node()].max(self.levels[rhs.node()]));
+        let res = AigLiteral::new(self.nodes.len() - 1, false);
+        self.strash.insert((lhs, rhs), res);
+        res
+    }
+
+    pub fn or(&mut self, lhs: AigLiteral, rhs: AigLiteral) -> AigLiteral {
+        !self.and(!lhs, !rhs)
+    }
+
+    pub fn xor(&mut self, lhs: AigLiteral, rhs: AigLiteral) -> AigLiteral {
+        let only_lhs = self.and(lhs, !rhs);
+        let only_rhs = self.and(!lhs, rhs);
+        self.or(only_lhs, only_rhs)
+    }
+
+    /// Simplifies the AND of an AND node's edge with another edge, or returns None.
+    fn rewrite(&mut self, lhs: AigLiteral, rhs: AigLiteral) -> Option<AigLiteral> {
+        let (a, b) = match self.nodes[lhs.node()] {
+            AigNode::And(a, b) => (a, b),
+            _ => return None,
+        };
+        if !lhs.is_complemented() {
+            if rhs == !a || rhs == !b {
+                // Contradiction: (a && b) && !a
+                return Some(AigLiteral::FALSE);
+            }
+            if rhs == a || rhs == b {
+                // Idempotence: (a && b) && a
+                return Some(lhs);
+            }
+            if let AigNode::And(c, d) = self.nodes[rhs.node()] {
+                if !rhs.is_complemented() && [c, d].iter().any(|edge| *edge == !a || *edge == !b) {
+                    // Contradiction between the operands: (a && b) && (!a && d)
+                    return Some(AigLiteral::FALSE);
+                }
+            }
+        } else {
+            if rhs == !a || rhs == !b {
+                // Subsumption: !(a && b) && !a
+                return Some(rhs);
+            }
+            if rhs == a {
+                // Resolution: !(a && b) && a
+                return Some(self.and(a, !b));
+            }
+            if rhs == b {
+                return Some(self.and(!a, b));
+            }
+        }
+        None
+    }
+
+    /// Evaluates an edge for an assignment of the inputs, indexed by variable.
+    pub fn evaluate(&self, root: AigLiteral, values: &[u8]) -> u8 {
+        let mut node_values = vec![0u8; root.node() + 1];
+        for (index, node) in self.nodes[..=root.node()].iter().enumerate() {
+            let edge_value =
+                |edge: AigLiteral| node_values[edge.node()] ^ edge.is_complemented() as u8;
+            node_values[index] = match node {
+                AigNode::Constant => 0,
+                AigNode::Input(id) => values[*id as usize],
+                AigNode::And(lhs, rhs) => edge_value(*lhs) & edge_value(*rhs),
+            };
+        }
+        node_values[root.node()] ^ root.is_complemented() as u8
+    }
+
+    /// Counts the AND nodes that an edge depends on.
+    pub fn and_count(&self, root: AigLiteral) -> usize {
+        self.reachable(root)
+            .iter()
+            .enumerate()
+            .filter(|(index, reached)| {
+                **reached && matches!(self.nodes[*index], AigNode::And(_, _))
+            })
+            .count()
+    }
+
+    /// Gets the number of AND nodes on the longest path from an input to an edge.
+    #[inline]
+    pub fn depth(&self, root: AigLiteral) -> usize {
+        self.levels[root.node()]
+    }
+
+    /// Rebuilds the function of an edge with the smallest depth for its AND trees.
+    /// The maximal trees of ANDs without complemented edges or shared nodes (supergates) are collected
+    /// and their inputs are combined again, always joining the two shallowest ones first, the way a
+    /// Huffman tree is built. Repeated inputs are combined once and complementary ones make the
+    /// supergate false. The balanced nodes are added to the graph and the edge of the result is returned.
+    pub fn balance(&mut self, root: AigLiteral) -> AigLiteral {
+        let reached = self.reachable(root);
+        let mut fanouts = vec![0; self.nodes.len()];
+        for (index, reached) in reached.iter().enumerate() {
+            if let (true, AigNode::And(lhs, rhs)) = (*reached, self.nodes[index]) {
+                fanouts[lhs.node()] += 1;
+                fanouts[rhs.node()] += 1;
+            }
+        }
+        // The supergates start at the root and at the ANDs that are shared or used complemented
+        let mut supergates = vec![false; self.nodes.len()];
+        supergates[root.node()] = true;
+        for (index, reached) in reached.iter().enumerate() {
+            if let (true, AigNode::And(lhs, rhs)) = (*reached, self.nodes[index]) {
+                for edge in [lhs, rhs] {
+                    if edge.is_complemented() || fanouts[edge.node()] != 1 {
+                        supergates[edge.node()] = true;
+                    }
+                }
+            }
+        }
+        // The operands of a node are stored before it, so going through the nodes in order balances
+        // the supergates below a supergate before it
+        let mut balanced = HashMap::new();
+        for (node, supergate) in supergates.into_iter().enumerate() {
+            if supergate && matches!(self.nodes[node], AigNode::And(_, _)) {
+                let res = self.balance_supergate(node, &fanouts, &balanced);
+                balanced.insert(node, res);
+            }
+        }
+        let res = match balanced.get(&root.node()) {
+            Some(res) => *res,
+            None => AigLiteral::new(root.node(), false),
+        };
+        if root.is_complemented() {
+            !res
+        } else {
+            res
+        }
+    }
+
+    /// Balances the supergate of an AND node, whose inputs that are ANDs have already been balanced.
+    fn balance_supergate(
+        &mut self,
+        node: usize,
+        fanouts: &[usize],
+        balanced: &HashMap<usize, AigLiteral>,
+    ) -> AigLiteral {
+        let mut leaves = Vec::new();
+        let mut stack = vec![AigLiteral::new(node, false)];
+        while let Some(edge) = stack.pop() {
+            match self.nodes[edge.node()] {
+                AigNode::And(lhs, rhs)
+                    if edge.node() == node
+                        || (!edge.is_complemented() && fanouts[edge.node()] == 1) =>
+                {
+                    stack.push(lhs);
+                    stack.push(rhs);
+                }
+                _ => leaves.push(edge),
+            }
+        }
+        let mut inputs: Vec<AigLiteral> = leaves
+            .into_iter()
+            .map(|leaf| {
+                let input = match balanced.get(&leaf.node()) {
+                    Some(input) => *input,
+                    None => AigLiteral::new(leaf.node(), false),
+                };
+                if leaf.is_complemented() {
+                    !input
+                } else {
+                    input
+                }
+            })
+            .collect();
+        inputs.sort_unstable();
+        inputs.dedup();
+        if inputs.windows(2).any(|pair| pair[1] == !pair[0]) {
+            return AigLiteral::FALSE;
+        }
+        let mut heap: BinaryHeap<Reverse<(usize, AigLiteral)>> = inputs
+            .into_iter()
+            .map(|input| Reverse((self.levels[input.node()], input)))
+            .collect();
+        while heap.len() > 1 {
+            let Reverse((_, lhs)) = heap.pop().unwrap();
+            let Reverse((_, rhs)) = heap.pop().unwrap();
+            let and = self.and(lhs, rhs);
+            heap.push(Reverse((self.levels[and.node()], and)));
+        }
+        heap.pop().unwrap().0 .1
+    }
+
+    /// Writes the function of an edge in the ASCII AIGER format, with the given names of the inputs.
+    /// Only the AND nodes that the edge depends on are written and they are numbered after the inputs.
+    pub fn to_aiger(&self, root: AigLiteral, variable_names: &[&str]) -> String {
+        let reached = self.reachable(root);
+        let mut numbers = vec![0; self.nodes.len()];
+        let mut ands = Vec::new();
+        let mut next_number = self.number_of_vars + 1;
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node {
+                AigNode::Constant => {}
+                AigNode::Input(id) => numbers[index] = *id as usize + 1,
+                AigNode::And(lhs, rhs) if reached[index] => {
+                    numbers[index] = next_number;
+                    next_number += 1;
+                    ands.push((index, *lhs, *rhs));
+                }
+                AigNode::And(_, _) => {}
+            }
+        }
+        let literal = |edge: AigLiteral| 2 * numbers[edge.node()] + edge.is_complemented() as usize;
+        let mut aiger = format!(
+            "aag {} {} 0 1 {}\n",
+            next_number - 1,
+            self.number_of_vars,
+            ands.len()
+        );
+        for id in 0..self.number_of_vars {
+            aiger.push_str(&format!("{}\n", 2 * (id + 1)));
+        }
+        aiger.push_str(&format!("{}\n", literal(root)));
+        for (index, lhs, rhs) in ands {
+            let (lhs, rhs) = (literal(lhs), literal(rhs));
+            aiger.push_str(&format!(
+                "{} {} {}\n",
+                2 * numbers[index],
+                lhs.max(rhs),
+                lhs.min(rhs)
+            ));
+        }
+        for (id, name) in variable_names.iter().enumerate() {
+            aiger.push_str(&format!("i{} {}\n", id, name));
+        }
+        aiger
+    }
+
+    /// Marks the nodes that an edge depends on.
+    fn reachable(&self, root: AigLiteral) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut stack = vec![root.node()];
+        while let Some(node) = stack.pop() {
+            if reached[node] {
+                continue;
+            }
+            reached[node] = true;
+            if let AigNode::And(lhs, rhs) = self.nodes[node] {
+                stack.push(lhs.node());
+                stack.push(rhs.node());
+            }
+        }
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitstring_trait::*;
+    use crate::parser::Parser;
+    use crate::rows::{RowOrder, Rows};
+    use crate::test_util::variable_name;
+    use logos::Logos;
+
+    fn assert_same_function(exp: &BooleanExpression, aig: &Aig, root: AigLiteral) {
+        let number_of_vars = exp.variables().len();
+        for (input, res) in Rows::new(exp, RowOrder::Binary) {
+            let values: Vec<u8> = (0..number_of_vars)
+                .map(|pos| input.get_bit(number_of_vars - 1 - pos).unwrap())
+                .collect();
+            assert_eq!(aig.evaluate(root, &values), res);
+        }
+    }
+
+    #[test]
+    fn test_hashing_and_rewriting() {
+        let exp = Parser::new(Token::lexer(
+            "(A && B || C) ^ (A && B || C) || A && B && !A",
+        ))
+        .parse()
+        .unwrap();
+        let (aig, root) = Aig::from_expression(&exp);
+        assert_eq!(root, AigLiteral::FALSE);
+        assert_eq!(aig.and_count(root), 0);
+        let exp = Parser::new(Token::lexer("!(A && B) && A || (A && B) && C"))
+            .parse()
+            .unwrap();
+        let (aig, root) = Aig::from_expression(&exp);
+        assert_eq!(aig.and_count(root), 4);
+        assert_same_function(&exp, &aig, root);
+    }
+
+    #[test]
+    fn test_balance() {
+        let exp = Parser::new(Token::lexer(
+            "A && B && C && D && E && F && G && H || !(A && B)",
+        ))
+        .parse()
+        .unwrap();
+        let (mut aig, root) = Aig::from_expression(&exp);
+        assert_eq!(aig.depth(root), 8);
+        let balanced = aig.balance(root);
+        assert_eq!(aig.depth(balanced), 4);
+        assert_eq!(aig.and_count(balanced), aig.and_count(root));
+        assert_same_function(&exp, &aig, balanced);
+
+        // Every level alternates between AND and OR, so there is one supergate per level
+        let n = 20_000;
+        let mut exp = "(".repeat(n - 1) + &variable_name(0);
+        for i in 1..n {
+            let op = if i % 2 == 0 { "&&" } else { "||" };
+            exp += &format!(" {} {})", op, variable_name(i));
+        }
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        let (mut aig, root) = Aig::from_expression(&exp);
+        assert_eq!(aig.depth(root), n - 1);
+        let balanced = aig.balance(root);
+        assert_eq!(aig.and_count(balanced), aig.and_count(root));
+        let mut values = vec![0; n];
+        assert_eq!(aig.evaluate(balanced, &values), 0);
+        values[n - 1] = 1;
+        assert_eq!(aig.evaluate(balanced, &values), 1);
+    }
+
+    #[test]
+    fn test_aiger() {
+        let exp = Parser::new(Token::lexer("A && !B")).parse().unwrap();
+        let (aig, root) = Aig::from_expression(&exp);
+        assert_eq!(
+            aig.to_aiger(root, exp.variables()),
+            "aag 3 2 0 1 1\n2\n4\n6\n6 5 2\ni0 A\ni1 B\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::boolean_expression::BooleanExpression;
+use crate::cnf::{Cnf, Literal};
+use crate::expression_graph::ExpressionGraph;
+use crate::sat::Solver;
+
+/// A lazy iterator over the satisfying assignments (models) of an expression that doesn't scan the truth table.
+/// Every model is found by the SAT solver and then excluded with a blocking clause before the next one is
+/// searched, so the work depends on the number of models instead of the number of rows.
+/// The items are indexed by variable id and are Some(value) for the variables that are assigned.
+/// By default every variable is assigned. With cubes, every model is shrunk to a partial assignment (a cube)
+/// where the unassigned variables are don't cares, because the expression is true for any value of them.
+/// The cubes are disjoint, so every model of the expression is covered by exactly one of them.
+pub struct Models {
+    solver: Solver,
+    graph: ExpressionGraph,
+    // The part of the expression that isn't covered by the cubes returned so far
+    remaining: usize,
+    number_of_vars: usize,
+    cubes: bool,
+}
+
+impl Models {
+    /// Creates an iterator over the models of an expression.
+    pub fn new(bexp: &BooleanExpression) -> Self {
+        let mut graph = ExpressionGraph::new();
+        let root = graph.add_expression(bexp);
+        let number_of_vars = bexp.variables().len();
+        let cnf = Cnf::from_graph(&graph, root, number_of_vars);
+        Self {
+            solver: Solver::from_cnf(&cnf),
+            graph,
+            remaining: root,
+            number_of_vars,
+            cubes: false,
+        }
+    }
+
+    /// Returns cubes with don't cares instead of complete assignments.
+    pub fn with_cubes(mut self) -> Self {
+        self.cubes = true;
+        self
+    }
+
+    /// Drops the variables of a model that the expression doesn't need, one at a time, as long as the rest
+    /// of the assignment still makes true the part of the expression that the previous cubes don't cover.
+    fn shrink(&mut self, model: &mut [Option<u8>]) {
+        for id in 0..self.number_of_vars {
+            let value = model[id].take();
+            let substitutions: Vec<Option<usize>> = model
+                .iter()
+                .map(|value| value.map(|value| self.graph.constant(value)))
+                .collect();
+            let rest = self.graph.substitute(self.remaining, &substitutions);
+            if self.graph.constant_value(rest) != Some(1) {
+                model[id] = value;
+            }
+        }
+    }
+}
+
+impl Iterator for Models {
+    type Item = Vec<Option<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let model = self.solver.solve()?;
+        let mut model: Vec<Option<u8>> = model[..self.number_of_vars]
+            .iter()
+            .map(|value| Some(*value as u8))
+            .collect();
+        if self.cubes {
+            self.shrink(&mut model);
+            let mut cube = self.graph.constant(1);
+            for (id, value) in model.iter().enumerate() {
+                if let Some(value) = value {
+                    let mut literal = self.graph.variable(id as u32);
+                    if *value == 0 {
+                        literal = self.graph.not(literal);
+                    }
+                    cube = self.graph.and(cube, literal);
+                }
+            }
+            let uncovered = self.graph.not(cube);
+            self.remaining = self.graph.and(self.remaining, uncovered);
+        }
+        let blocking_clause: Vec<Literal> = model
+            .iter()
+            .enumerate()
+            .filter_map(|(id, value)| value.map(|value| Literal::new(id as u32, value == 1)))
+            .collect();
+        self.solver.add_clause(&blocking_clause);
+        Some(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_models() {
+        let exp = Parser::new(Token::lexer("A && !B || C")).parse().unwrap();
+        let mut models: Vec<Vec<Option<u8>>> = Models::new(&exp).collect();
+        models.sort();
+        let expected: Vec<Vec<Option<u8>>> = [1, 3, 4, 5, 7]
+            .iter()
+            .map(|row| (0..3).map(|id| Some(row >> (2 - id) & 1)).collect())
+            .collect();
+        assert_eq!(models, expected);
+    }
+
+    #[test]
+    fn test_cubes_cover_the_models_once() {
+        let exp = Parser::new(Token::lexer("A && !B || C && D"))
+            .parse()
+            .unwrap();
+        let cubes: Vec<Vec<Option<u8>>> = Models::new(&exp).with_cubes().collect();
+        let covered: u32 = cubes
+            .iter()
+            .map(|cube| 1 << cube.iter().filter(|value| value.is_none()).count())
+            .sum();
+        assert_eq!(covered, 7);
+        assert!(cubes.len() < 7);
+    }
+}
@@ -0,0 +1,785 @@
+use crate::big_uint::BigUint;
+use crate::boolean_expression::*;
+use crate::token::*;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A function of a BddManager, identified by its top node.
+/// Since the diagrams are reduced and ordered, two functions of the same manager are equal
+/// exactly when their handles are equal.
+pub struct Bdd(usize);
+
+impl Bdd {
+    pub const FALSE: Bdd = Bdd(0);
+    pub const TRUE: Bdd = Bdd(1);
+
+    #[inline]
+    pub fn is_constant(self) -> bool {
+        self.0 < 2
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BddNode {
+    var: u32,
+    low: Bdd,
+    high: Bdd,
+}
+
+/// The variable of the two terminal nodes, which are below every variable.
+const TERMINAL: u32 = u32::MAX;
+/// The variable of the slots of nodes that were garbage collected, which are reused by new nodes.
+const FREE: u32 = u32::MAX - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// A static heuristic for the order of the variables of a diagram, which is computed from the structure
+/// of the expression before the diagram is built.
+/// Appearance is the order in which the variables first appear in the expression (the order of their ids).
+/// DepthFirst visits the operands of every operator deeper first, so the variables of the biggest parts of
+/// the expression are tested next to each other. Interleaved visits the operands the same way, but alternates
+/// between the variables of the two operands of every binary operator, which keeps the variables that play
+/// the same role in both operands close.
+pub enum VariableOrder {
+    #[default]
+    Appearance,
+    DepthFirst,
+    Interleaved,
+}
+
+/// Computes an order of the variables of an expression with a static heuristic.
+/// The order is a permutation of the variable ids, from the first variable that is tested to the last one.
+pub fn variable_order(bexp: &BooleanExpression, heuristic: VariableOrder) -> Vec<u32> {
+    let number_of_vars = bexp.variables().len() as u32;
+    if heuristic == VariableOrder::Appearance {
+        return (0..number_of_vars).collect();
+    }
+    let (_, mut order) = bexp.fold(|node| match node {
+        Node::Variable(id) => (1, vec![id]),
+        Node::Constant(_) => (1, Vec::new()),
+        Node::Unary(_, (depth, vars)) => (depth + 1, vars),
+        Node::Binary(_, lhs, rhs) => {
+            let (first, second) = if rhs.0 > lhs.0 {
+                (rhs, lhs)
+            } else {
+                (lhs, rhs)
+            };
+            let vars = merge(first.1, second.1, heuristic == VariableOrder::Interleaved);
+            (first.0 + 1, vars)
+        }
+    });
+    // Variables that don't appear in the expression anymore (i.e after fixing them) go last
+    let seen: HashSet<u32> = order.iter().copied().collect();
+    order.extend((0..number_of_vars).filter(|id| !seen.contains(id)));
+    order
+}
+
+/// Merges the variable orders of two operands without duplicates, either by appending the second one
+/// to the first one or by alternating between them.
+fn merge(first: Vec<u32>, second: Vec<u32>, interleave: bool) -> Vec<u32> {
+    let merged: Vec<u32> = if interleave {
+        let mut merged = Vec::with_capacity(first.len() + second.len());
+        for i in 0..first.len().max(second.len()) {
+            merged.extend(first.get(i));
+            merged.extend(second.get(i));
+        }
+        merged
+    } else {
+        first.into_iter().chain(second).collect()
+    };
+    let mut seen = HashSet::new();
+    merged.into_iter().filter(|id| seen.insert(*id)).collect()
+}
+
+/// The bookkeeping of sifting, which keeps the nodes of every variable and the number of references to
+/// every node (from other nodes and from the roots), so that a swap of two levels only visits their nodes
+/// and the number of live nodes is known after every swap.
+struct Sifting {
+    var_nodes: Vec<Vec<usize>>,
+    refs: Vec<usize>,
+    live: usize,
+}
+
+/// A manager of reduced ordered binary decision diagrams (ROBDD).
+/// A diagram is a graph where every node tests a variable and continues to its low (0) or high (1) child,
+/// the variables are tested in the same order on every path and there are no redundant nodes, which makes
+/// the diagram of a function canonical for the order. Nodes are only created through the unique table,
+/// so identical subgraphs are stored once and are shared by all the functions of the manager.
+/// Every operation is built on ite (if-then-else), whose results are kept in the computed table.
+/// The position of a variable in the order is its level. By default the variables are tested in the order
+/// of their ids, but any order can be given and it can be changed later by sifting (see sift).
+pub struct BddManager {
+    nodes: Vec<BddNode>,
+    unique: HashMap<BddNode, Bdd>,
+    computed: HashMap<(Bdd, Bdd, Bdd), Bdd>,
+    // The slots of the garbage collected nodes
+    free: Vec<usize>,
+    // The variable of every level and the level of every variable
+    order: Vec<u32>,
+    levels: Vec<usize>,
+}
+
+impl BddManager {
+    /// Creates a manager for functions over the given number of variables.
+    pub fn new(number_of_vars: usize) -> Self {
+        Self::with_order((0..number_of_vars as u32).collect())
+    }
+
+    /// Creates a manager where the variables are tested in the given order, which must be a permutation
+    /// of the variable ids (see variable_order).
+    pub fn with_order(order: Vec<u32>) -> Self {
+        let terminal = |value: usize| BddNode {
+            var: TERMINAL,
+            low: Bdd(value),
+            high: Bdd(value),
+        };
+        let mut levels = vec![0; order.len()];
+        for (level, var) in order.iter().enumerate() {
+            levels[*var as usize] = level;
+        }
+        Self {
+            nodes: vec![terminal(0), terminal(1)],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+            free: Vec::new(),
+            order,
+            levels,
+        }
+    }
+
+    /// Creates a manager over the variables of an expression and builds the expression's diagram in it.
+    pub fn from_expression(bexp: &BooleanExpression) -> (Self, Bdd) {
+        Self::from_expression_with_order(bexp, VariableOrder::Appearance)
+    }
+
+    /// Same as from_expression but orders the variables with a static heuristic.
+    pub fn from_expression_with_order(
+        bexp: &BooleanExpression,
+        heuristic: VariableOrder,
+    ) -> (Self, Bdd) {
+        let mut manager = Self::with_order(variable_order(bexp, heuristic));
+        let root = manager.add_expression(bexp);
+        (manager, root)
+    }
+
+    /// Builds the diagram of an expression whose variable ids are variables of the manager.
+    /// The parser chains the operands of an operator from left to right, i.e "A && B && C && D" is
+    /// "((A && B) && C) && D", and building a long chain in that order rebuilds the diagram of the left
+    /// operand at every step. So the operands of every chain of the same operator are collected and
+    /// combined pairwise instead, as a balanced tree.
+    pub fn add_expression(&mut self, bexp: &BooleanExpression) -> Bdd {
+        let chain = bexp.fold(|node| match node {
+            Node::Variable(id) => (None, vec![self.var(id)]),
+            Node::Constant(value) => (None, vec![self.constant(value)]),
+            Node::Unary(_, operand) => {
+                let f = self.combine(operand);
+                (None, vec![self.not(f)])
+            }
+            Node::Binary(op, lhs, rhs) => {
+                let mut operands = Vec::new();
+                for operand in [lhs, rhs] {
+                    if operand.0 == Some(op) {
+                        operands.extend(operand.1);
+                    } else {
+                        operands.push(self.combine(operand));
+                    }
+                }
+                (Some(op), operands)
+            }
+        });
+        self.combine(chain)
+    }
+
+    /// Combines the operands of a chain of the same operator (None for a single function) pairwise.
+    fn combine(&mut self, (op, mut operands): (Option<Token>, Vec<Bdd>)) -> Bdd {
+        while operands.len() > 1 {
+            operands = operands
+                .chunks(2)
+                .map(|pair| match (op, pair) {
+                    (Some(Token::AND), [f, g]) => self.and(*f, *g),
+                    (Some(Token::OR), [f, g]) => self.or(*f, *g),
+                    (_, [f, g]) => self.xor(*f, *g),
+                    (_, _) => pair[0],
+                })
+                .collect();
+        }
+        operands[0]
+    }
+
+    #[inline]
+    pub fn number_of_vars(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Gets the variables from the first one that is tested to the last one.
+    #[inline]
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    #[inline]
+    pub fn constant(&self, value: u8) -> Bdd {
+        if value == 0 {
+            Bdd::FALSE
+        } else {
+            Bdd::TRUE
+        }
+    }
+
+    /// Gets the function that is true when the variable is true.
+    pub fn var(&mut self, id: u32) -> Bdd {
+        self.make(id, Bdd::FALSE, Bdd::TRUE)
+    }
+
+    /// Gets the variable that the top node of a function tests, or None for constants.
+    pub fn top_var(&self, f: Bdd) -> Option<u32> {
+        if f.is_constant() {
+            None
+        } else {
+            Some(self.nodes[f.0].var)
+        }
+    }
+
+    /// Gets the low (variable is 0) and high (variable is 1) children of the top node of a function.
+    pub fn children(&self, f: Bdd) -> (Bdd, Bdd) {
+        let node = self.nodes[f.0];
+        (node.low, node.high)
+    }
+
+    /// Computes if f then g else h.
+    /// The cofactors are computed on an explicit stack, since the calls go as deep as the number of variables.
+    /// Every call is visited twice: first to split it on the top variable and then to combine the results
+    /// of its cofactors, which are on the results stack.
+    pub fn ite(&mut self, f: Bdd, g: Bdd, h: Bdd) -> Bdd {
+        let mut calls = vec![(f, g, h, false)];
+        let mut results = Vec::new();
+        while let Some((f, g, h, split)) = calls.pop() {
+            let level = self.level(f).min(self.level(g)).min(self.level(h));
+            if split {
+                let high = results.pop().unwrap();
+                let low = results.pop().unwrap();
+                let res = self.make(self.order[level], low, high);
+                self.computed.insert((f, g, h), res);
+                results.push(res);
+                continue;
+            }
+            let res = match (f, g, h) {
+                (Bdd::TRUE, _, _) => Some(g),
+                (Bdd::FALSE, _, _) => Some(h),
+                _ if g == h => Some(g),
+                (_, Bdd::TRUE, Bdd::FALSE) => Some(f),
+                _ => self.computed.get(&(f, g, h)).copied(),
+            };
+            if let Some(res) = res {
+                results.push(res);
+                continue;
+            }
+            let var = self.order[level];
+            let (f_low, f_high) = self.cofactors(f, var);
+            let (g_low, g_high) = self.cofactors(g, var);
+            let (h_low, h_high) = self.cofactors(h, var);
+            calls.push((f, g, h, true));
+            calls.push((f_high, g_high, h_high, false));
+            calls.push((f_low, g_low, h_low, false));
+        }
+        results.pop().unwrap()
+    }
+
+    pub fn not(&mut self, f: Bdd) -> Bdd {
+        self.ite(f, Bdd::FALSE, Bdd::TRUE)
+    }
+
+    pub fn and(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.ite(f, g, Bdd::FALSE)
+    }
+
+    pub fn or(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.ite(f, Bdd::TRUE, g)
+    }
+
+    pub fn xor(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        let not_g = self.not(g);
+        self.ite(f, not_g, g)
+    }
+
+    /// Substitutes a constant for a variable.
+    pub fn restrict(&mut self, f: Bdd, var: u32, value: u8) -> Bdd {
+        let mut memo = HashMap::new();
+        // The nodes are rebuilt on an explicit stack, the same way as in ite
+        let mut calls = vec![(f, false)];
+        let mut results = Vec::new();
+        while let Some((f, split)) = calls.pop() {
+            let node = self.nodes[f.0];
+            if split {
+                let high = results.pop().unwrap();
+                let low = results.pop().unwrap();
+                let res = self.make(node.var, low, high);
+                memo.insert(f, res);
+                results.push(res);
+            } else if self.level(f) > self.levels[var as usize] {
+                // Terminals and nodes below the variable don't depend on it
+                results.push(f);
+            } else if node.var == var {
+                results.push(if value == 0 { node.low } else { node.high });
+            } else if let Some(res) = memo.get(&f) {
+                results.push(*res);
+            } else {
+                calls.push((f, true));
+                calls.push((node.high, false));
+                calls.push((node.low, false));
+            }
+        }
+        results.pop().unwrap()
+    }
+
+    /// Existential quantification: true for the assignments of the other variables where
+    /// f is true for some value of var.
+    pub fn exists(&mut self, f: Bdd, var: u32) -> Bdd {
+        let low = self.restrict(f, var, 0);
+        let high = self.restrict(f, var, 1);
+        self.or(low, high)
+    }
+
+    /// Universal quantification: true for the assignments of the other variables where
+    /// f is true for both values of var.
+    pub fn forall(&mut self, f: Bdd, var: u32) -> Bdd {
+        let low = self.restrict(f, var, 0);
+        let high = self.restrict(f, var, 1);
+        self.and(low, high)
+    }
+
+    /// Substitutes the function g for the variable var in f.
+    pub fn compose(&mut self, f: Bdd, var: u32, g: Bdd) -> Bdd {
+        let low = self.restrict(f, var, 0);
+        let high = self.restrict(f, var, 1);
+        self.ite(g, high, low)
+    }
+
+    /// Evaluates a function for an assignment of the variables, indexed by variable.
+    pub fn evaluate(&self, f: Bdd, values: &[u8]) -> u8 {
+        let mut f = f;
+        while !f.is_constant() {
+            let node = self.nodes[f.0];
+            f = if values[node.var as usize] == 0 {
+                node.low
+            } else {
+                node.high
+            };
+        }
+        f.0 as u8
+    }
+
+    /// Counts the assignments of all the variables of the manager that make the function true.
+    pub fn sat_count(&self, f: Bdd) -> BigUint {
+        // The models of every node over the variables from its top variable down, which are counted
+        // on an explicit stack after the ones of its children
+        let mut memo: HashMap<Bdd, BigUint> = HashMap::new();
+        let models = |memo: &HashMap<Bdd, BigUint>, f: Bdd| {
+            if f.is_constant() {
+                BigUint::from(f.0 as u64)
+            } else {
+                memo[&f].clone()
+            }
+        };
+        let mut stack = vec![(f, false)];
+        while let Some((g, split)) = stack.pop() {
+            if g.is_constant() || memo.contains_key(&g) {
+                continue;
+            }
+            let node = self.nodes[g.0];
+            if split {
+                let level = self.level(g);
+                let low = models(&memo, node.low) << (self.level(node.low) - level - 1);
+                let high = models(&memo, node.high) << (self.level(node.high) - level - 1);
+                memo.insert(g, &low + &high);
+            } else {
+                stack.push((g, true));
+                stack.push((node.high, false));
+                stack.push((node.low, false));
+            }
+        }
+        models(&memo, f) << self.level(f)
+    }
+
+    /// Computes the influence of every variable on the function, indexed by id, which is the probability
+    /// that flipping the variable flips the function when all the variables are uniformly random.
+    /// A random assignment follows a single path of the diagram and flipping x only matters on the paths that
+    /// go through a node of x, where it switches between the children of the node. So the influence of x is
+    /// the sum over the nodes of x of the probability of reaching the node times the probability that its
+    /// children differ, which takes a single pass over the diagram from the top down.
+    pub fn influences(&mut self, f: Bdd) -> Vec<f64> {
+        let mut influences = vec![0.0; self.number_of_vars()];
+        let mut nodes: Vec<Bdd> = self
+            .reachable(&[f])
+            .into_iter()
+            .enumerate()
+            .filter(|(index, reached)| *reached && !Bdd(*index).is_constant())
+            .map(|(index, _)| Bdd(index))
+            .collect();
+        nodes.sort_unstable_by_key(|g| self.level(*g));
+        let mut reach: HashMap<Bdd, f64> = HashMap::new();
+        reach.insert(f, 1.0);
+        let mut memo = HashMap::new();
+        for g in nodes {
+            let node = self.nodes[g.0];
+            let g_reach = reach[&g];
+            let difference = self.xor(node.low, node.high);
+            influences[node.var as usize] += g_reach * self.probability(difference, &mut memo);
+            for child in [node.low, node.high] {
+                *reach.entry(child).or_insert(0.0) += g_reach / 2.0;
+            }
+        }
+        influences
+    }
+
+    /// Computes the probability that a function is true when all the variables are uniformly random,
+    /// keeping the probabilities of the nodes in the given map. The nodes are visited on an explicit stack
+    /// after their children.
+    fn probability(&self, f: Bdd, memo: &mut HashMap<Bdd, f64>) -> f64 {
+        let probability = |memo: &HashMap<Bdd, f64>, f: Bdd| {
+            if f.is_constant() {
+                f.0 as f64
+            } else {
+                memo[&f]
+            }
+        };
+        let mut stack = vec![(f, false)];
+        while let Some((g, split)) = stack.pop() {
+            if g.is_constant() || memo.contains_key(&g) {
+                continue;
+            }
+            let node = self.nodes[g.0];
+            if split {
+                let res = (probability(memo, node.low) + probability(memo, node.high)) / 2.0;
+                memo.insert(g, res);
+            } else {
+                stack.push((g, true));
+                stack.push((node.high, false));
+                stack.push((node.low, false));
+            }
+        }
+        probability(memo, f)
+    }
+
+    /// Finds an assignment that makes the function true, as a cube indexed by variable where
+    /// the variables that are not on the path are don't cares (None). Returns None for the false function.
+    pub fn any_sat(&self, f: Bdd) -> Option<Vec<Option<u8>>> {
+        if f == Bdd::FALSE {
+            return None;
+        }
+        let mut cube = vec![None; self.number_of_vars()];
+        let mut f = f;
+        while !f.is_constant() {
+            let node = self.nodes[f.0];
+            // Every node other than the false terminal has a path to the true terminal
+            if node.high != Bdd::FALSE {
+                cube[node.var as usize] = Some(1);
+                f = node.high;
+            } else {
+                cube[node.var as usize] = Some(0);
+                f = node.low;
+            }
+        }
+        Some(cube)
+    }
+
+    /// Counts the nodes of the diagram of a function, including the terminals that it reaches.
+    pub fn node_count(&self, f: Bdd) -> usize {
+        self.shared_node_count(&[f])
+    }
+
+    /// Counts the nodes of the diagrams of several functions, where the shared nodes are counted once.
+    pub fn shared_node_count(&self, roots: &[Bdd]) -> usize {
+        self.reachable(roots)
+            .iter()
+            .filter(|reached| **reached)
+            .count()
+    }
+
+    /// Reorders the variables to make the diagrams of the given functions smaller, with Rudell's sifting.
+    /// Every variable, starting from the ones with the most nodes, is moved through all the levels by
+    /// swapping it with its neighbours and is left at the level where the diagrams were the smallest.
+    /// The handles of the roots stay valid, since swapping levels rewrites the nodes in place, but the
+    /// nodes that the roots don't reach are garbage collected, so any other handles become invalid.
+    pub fn sift(&mut self, roots: &[Bdd]) {
+        self.collect_garbage(roots);
+        let mut sifting = Sifting {
+            var_nodes: vec![Vec::new(); self.number_of_vars()],
+            refs: vec![0; self.nodes.len()],
+            live: 0,
+        };
+        for (index, node) in self.nodes.iter().enumerate().skip(2) {
+            if node.var != FREE {
+                sifting.var_nodes[node.var as usize].push(index);
+                sifting.refs[node.low.0] += 1;
+                sifting.refs[node.high.0] += 1;
+                sifting.live += 1;
+            }
+        }
+        for root in roots {
+            sifting.refs[root.0] += 1;
+        }
+        let mut vars: Vec<u32> = (0..self.number_of_vars() as u32).collect();
+        vars.sort_by_key(|var| Reverse(sifting.var_nodes[*var as usize].len()));
+        for var in vars {
+            let mut level = self.levels[var as usize];
+            let (mut best_size, mut best_level) = (sifting.live, level);
+            for down in [true, false] {
+                while (down && level + 1 < self.number_of_vars()) || (!down && level > 0) {
+                    if down {
+                        self.swap_levels(level, &mut sifting);
+                        level += 1;
+                    } else {
+                        self.swap_levels(level - 1, &mut sifting);
+                        level -= 1;
+                    }
+                    if sifting.live < best_size {
+                        best_size = sifting.live;
+                        best_level = level;
+                    }
+                }
+            }
+            while level < best_level {
+                self.swap_levels(level, &mut sifting);
+                level += 1;
+            }
+        }
+    }
+
+    /// Swaps the variables of a level and the next one.
+    /// The nodes of the upper variable x that have children with the lower variable y are rewritten in place
+    /// to test y first, with new nodes of x below them, so every node keeps representing the same function.
+    /// The nodes of x without children of y and the nodes of y just move a level, so only the nodes of the
+    /// two variables are visited. The nodes of y that are no longer used afterwards are freed.
+    fn swap_levels(&mut self, level: usize, sifting: &mut Sifting) {
+        let (x, y) = (self.order[level], self.order[level + 1]);
+        self.order.swap(level, level + 1);
+        self.levels[x as usize] = level + 1;
+        self.levels[y as usize] = level;
+        let x_nodes = std::mem::take(&mut sifting.var_nodes[x as usize]);
+        let mut old_y_nodes = std::mem::take(&mut sifting.var_nodes[y as usize]);
+        for index in x_nodes {
+            let node = self.nodes[index];
+            if self.nodes[node.low.0].var != y && self.nodes[node.high.0].var != y {
+                sifting.var_nodes[x as usize].push(index);
+                continue;
+            }
+            let (low_low, low_high) = self.cofactors(node.low, y);
+            let (high_low, high_high) = self.cofactors(node.high, y);
+            // The node depends on both variables, so the new children are different nodes
+            let low = self.make_counted(x, low_low, high_low, sifting);
+            let high = self.make_counted(x, low_high, high_high, sifting);
+            // The new children are referenced before the old ones are released, so the nodes that
+            // they share are never freed
+            sifting.refs[low.0] += 1;
+            sifting.refs[high.0] += 1;
+            sifting.refs[node.low.0] -= 1;
+            sifting.refs[node.high.0] -= 1;
+            let swapped = BddNode { var: y, low, high };
+            self.unique.remove(&node);
+            self.unique.insert(swapped, Bdd(index));
+            self.nodes[index] = swapped;
+            sifting.var_nodes[y as usize].push(index);
+        }
+        // The old nodes of y were only referenced by nodes of x and the roots, and their children are
+        // also the children of the new nodes of x, so freeing them doesn't free anything else
+        old_y_nodes.retain(|index| {
+            if sifting.refs[*index] > 0 {
+                return true;
+            }
+            let node = self.nodes[*index];
+            sifting.refs[node.low.0] -= 1;
+            sifting.refs[node.high.0] -= 1;
+            self.unique.remove(&node);
+            self.nodes[*index].var = FREE;
+            self.free.push(*index);
+            sifting.live -= 1;
+            false
+        });
+        sifting.var_nodes[y as usize].append(&mut old_y_nodes);
+    }
+
+    /// Same as make, but keeps the node lists and the reference counts of sifting up to date.
+    fn make_counted(&mut self, var: u32, low: Bdd, high: Bdd, sifting: &mut Sifting) -> Bdd {
+        let existing = low == high || self.unique.contains_key(&BddNode { var, low, high });
+        let res = self.make(var, low, high);
+        if !existing {
+            if sifting.refs.len() < self.nodes.len() {
+                sifting.refs.resize(self.nodes.len(), 0);
+            }
+            sifting.refs[res.0] = 0;
+            sifting.refs[low.0] += 1;
+            sifting.refs[high.0] += 1;
+            sifting.var_nodes[var as usize].push(res.0);
+            sifting.live += 1;
+        }
+        res
+    }
+
+    /// Frees the nodes that the roots don't reach, so that their slots are reused, and clears the
+    /// computed table which may refer to them.
+    fn collect_garbage(&mut self, roots: &[Bdd]) {
+        let reached = self.reachable(roots);
+        for (index, reached) in reached.into_iter().enumerate().skip(2) {
+            let node = self.nodes[index];
+            if !reached && node.var != FREE {
+                self.unique.remove(&node);
+                self.nodes[index].var = FREE;
+                self.free.push(index);
+            }
+        }
+        self.computed.clear();
+    }
+
+    /// Marks the nodes that are reachable from the roots.
+    fn reachable(&self, roots: &[Bdd]) -> Vec<bool> {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut stack = roots.to_vec();
+        while let Some(f) = stack.pop() {
+            if reached[f.0] {
+                continue;
+            }
+            reached[f.0] = true;
+            if !f.is_constant() {
+                let node = self.nodes[f.0];
+                stack.push(node.low);
+                stack.push(node.high);
+            }
+        }
+        reached
+    }
+
+    /// Gets the node that tests var with the given children, creating it if it doesn't exist.
+    fn make(&mut self, var: u32, low: Bdd, high: Bdd) -> Bdd {
+        if low == high {
+            return low;
+        }
+        let node = BddNode { var, low, high };
+        let (nodes, free) = (&mut self.nodes, &mut self.free);
+        *self.unique.entry(node).or_insert_with(|| match free.pop() {
+            Some(index) => {
+                nodes[index] = node;
+                Bdd(index)
+            }
+            None => {
+                nodes.push(node);
+                Bdd(nodes.len() - 1)
+            }
+        })
+    }
+
+    /// Gets the position of the top variable of a function in the order, or the number of variables
+    /// for constants.
+    #[inline]
+    fn level(&self, f: Bdd) -> usize {
+        if f.is_constant() {
+            self.number_of_vars()
+        } else {
+            self.levels[self.nodes[f.0].var as usize]
+        }
+    }
+
+    /// Gets the cofactors of a function with respect to a variable that is not below its top variable.
+    #[inline]
+    fn cofactors(&self, f: Bdd, var: u32) -> (Bdd, Bdd) {
+        let node = self.nodes[f.0];
+        if node.var == var {
+            (node.low, node.high)
+        } else {
+            (f, f)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitstring_trait::*;
+    use crate::parser::Parser;
+    use crate::rows::{RowOrder, Rows};
+    use logos::Logos;
+
+    #[test]
+    fn test_canonical_form() {
+        let exp = Parser::new(Token::lexer("!(A && B) || C ^ (A && C)"))
+            .parse()
+            .unwrap();
+        let (mut manager, f) = BddManager::from_expression(&exp);
+        let same = Parser::new(Token::lexer("!A || !(B || C)"))
+            .parse()
+            .unwrap();
+        assert_eq!(manager.add_expression(&same), f);
+        for (input, res) in Rows::new(&exp, RowOrder::Binary) {
+            let values: Vec<u8> = (0..3).map(|pos| input.get_bit(2 - pos).unwrap()).collect();
+            assert_eq!(manager.evaluate(f, &values), res);
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        let (mut manager, f) = BddManager::from_expression(&exp);
+        let (a, b, c) = (manager.var(0), manager.var(1), manager.var(2));
+        assert_eq!(manager.sat_count(f), BigUint::from(5));
+        assert_eq!(manager.restrict(f, 2, 1), Bdd::TRUE);
+        assert_eq!(manager.restrict(f, 0, 0), c);
+        let b_or_c = manager.or(b, c);
+        assert_eq!(manager.exists(f, 0), b_or_c);
+        assert_eq!(manager.forall(f, 0), c);
+        let not_a = manager.not(a);
+        let not_a_and_b = manager.and(not_a, b);
+        let composed = manager.compose(f, 2, not_a);
+        let expected = manager.or(not_a, not_a_and_b);
+        let a_and_b = manager.and(a, b);
+        let expected = manager.or(expected, a_and_b);
+        assert_eq!(composed, expected);
+        let cube = manager.any_sat(f).unwrap();
+        assert_eq!(cube, vec![Some(1), Some(1), None]);
+        assert_eq!(manager.node_count(f), 5);
+    }
+
+    #[test]
+    fn test_deep_diagram() {
+        // (x0 || !x1) && (x1 || !x2) && ... is true when the true variables come first
+        let n = 30_000;
+        let mut manager = BddManager::new(n);
+        let mut f = Bdd::TRUE;
+        for var in (0..n as u32 - 1).rev() {
+            let (x, y) = (manager.var(var), manager.var(var + 1));
+            let not_y = manager.not(y);
+            let clause = manager.or(x, not_y);
+            f = manager.and(clause, f);
+        }
+        assert_eq!(manager.sat_count(f), BigUint::from(n as u64 + 1));
+        let last = manager.var(n as u32 - 1);
+        let g = manager.and(f, last);
+        assert_eq!(manager.sat_count(g), BigUint::from(1));
+        // Only the last variable is left free when it can't be true
+        let all_true = manager.restrict(f, n as u32 - 1, 1);
+        assert_eq!(manager.sat_count(all_true), BigUint::from(2));
+    }
+
+    #[test]
+    fn test_reordering() {
+        let exp = Parser::new(Token::lexer(
+            "(A || B || C || D) && (A && E || B && F || C && G || D && H)",
+        ))
+        .parse()
+        .unwrap();
+        let (dfs_manager, dfs_root) =
+            BddManager::from_expression_with_order(&exp, VariableOrder::DepthFirst);
+        assert_eq!(dfs_manager.order(), &[0, 4, 1, 5, 2, 6, 3, 7]);
+        assert_eq!(dfs_manager.node_count(dfs_root), 10);
+        let (mut manager, f) = BddManager::from_expression(&exp);
+        assert_eq!(manager.node_count(f), 32);
+        manager.sift(&[f]);
+        assert_eq!(manager.node_count(f), 10);
+        assert_eq!(manager.sat_count(f), BigUint::from(175));
+        for (input, res) in Rows::new(&exp, RowOrder::Binary) {
+            let values: Vec<u8> = (0..8).map(|pos| input.get_bit(7 - pos).unwrap()).collect();
+            assert_eq!(manager.evaluate(f, &values), res);
+        }
+        // The diagrams are still canonical in the new order
+        assert_eq!(manager.add_expression(&exp), f);
+    }
+}
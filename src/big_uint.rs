@@ -0,0 +1,235 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Shl, Sub};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// An arbitrary precision unsigned integer, for counts that don't fit in a u128
+/// (i.e the number of satisfying rows of an expression with hundreds of variables).
+/// The digits are stored in base 2^32, least significant first, without leading zeros.
+pub struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn one() -> Self {
+        Self::from(1u64)
+    }
+
+    /// Creates the number 2^exponent.
+    pub fn power_of_two(exponent: usize) -> Self {
+        Self::one() << exponent
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// Gets the number of bits without the leading zeros.
+    pub fn bits(&self) -> usize {
+        match self.digits.last() {
+            Some(last) => 32 * self.digits.len() - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    /// Divides the number by 2^exponent and returns the result as a float.
+    /// This is how a count of rows is turned into a fraction of the truth table.
+    pub fn div_power_of_two(&self, exponent: usize) -> f64 {
+        // Only the 64 most significant bits fit in the precision of a f64 anyway
+        let bits = self.bits();
+        let shift = bits.saturating_sub(64);
+        let mut mantissa = 0u64;
+        for i in (shift..bits).rev() {
+            mantissa = mantissa << 1 | self.bit(i) as u64;
+        }
+        mantissa as f64 * 2f64.powi(shift as i32 - exponent as i32)
+    }
+
+    fn bit(&self, i: usize) -> u32 {
+        self.digits[i / 32] >> (i % 32) & 1
+    }
+
+    fn trim(mut self) -> Self {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        self
+    }
+
+    /// Divides the number by a small divisor in place and returns the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for digit in self.digits.iter_mut().rev() {
+            let current = remainder << 32 | *digit as u64;
+            *digit = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+        remainder as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        BigUint {
+            digits: vec![value as u32, (value >> 32) as u32],
+        }
+        .trim()
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Add<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.digits.len().max(other.digits.len());
+        let mut digits = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len {
+            let sum = *self.digits.get(i).unwrap_or(&0) as u64
+                + *other.digits.get(i).unwrap_or(&0) as u64
+                + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        BigUint { digits }.trim()
+    }
+}
+
+impl<'a> Sub<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    /// Subtracts a number that is not bigger than self.
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(*self >= *other, "BigUint subtraction underflow");
+        let mut digits = Vec::with_capacity(self.digits.len());
+        let mut borrow = 0i64;
+        for i in 0..self.digits.len() {
+            let mut difference =
+                self.digits[i] as i64 - *other.digits.get(i).unwrap_or(&0) as i64 - borrow;
+            borrow = 0;
+            if difference < 0 {
+                difference += 1 << 32;
+                borrow = 1;
+            }
+            digits.push(difference as u32);
+        }
+        BigUint { digits }.trim()
+    }
+}
+
+impl<'a> Mul<&'a BigUint> for &'a BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, lhs) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, rhs) in other.digits.iter().enumerate() {
+                let product = *lhs as u64 * *rhs as u64 + digits[i + j] as u64 + carry;
+                digits[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        BigUint { digits }.trim()
+    }
+}
+
+impl Shl<usize> for BigUint {
+    type Output = BigUint;
+
+    fn shl(self, shift: usize) -> BigUint {
+        if self.is_zero() {
+            return self;
+        }
+        let (words, bits) = (shift / 32, shift % 32);
+        let mut digits = vec![0u32; words];
+        let mut carry = 0u32;
+        for digit in &self.digits {
+            if bits == 0 {
+                digits.push(*digit);
+            } else {
+                digits.push(digit << bits | carry);
+                carry = digit >> (32 - bits);
+            }
+        }
+        digits.push(carry);
+        BigUint { digits }.trim()
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.pad("0");
+        }
+        // Convert to base 10^9 and print the chunks with leading zeros, except the first one
+        let mut number = self.clone();
+        let mut chunks = Vec::new();
+        while !number.is_zero() {
+            chunks.push(number.div_rem_small(1_000_000_000));
+        }
+        let mut text = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            text.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let a = BigUint::from(u64::MAX);
+        let b = BigUint::from(3);
+        assert_eq!((&a * &b).to_string(), (u64::MAX as u128 * 3).to_string());
+        assert_eq!((&a + &b).to_string(), (u64::MAX as u128 + 3).to_string());
+        assert_eq!(
+            (&(&a * &a) - &a).to_string(),
+            "340282366920938463408034375210639556610"
+        );
+        assert_eq!(&(&a + &b) - &(&a + &b), BigUint::zero());
+        assert!(a > b);
+    }
+
+    #[test]
+    fn test_powers_of_two() {
+        assert_eq!(
+            BigUint::power_of_two(100).to_string(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(BigUint::power_of_two(100).bits(), 101);
+        let three_quarters = &BigUint::power_of_two(300) - &BigUint::power_of_two(298);
+        assert_eq!(three_quarters.div_power_of_two(300), 0.75);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::bitstring_trait::*;
+use std::cmp::Ordering;
+
+const WORD_BITS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A growable bitstring backed by 64-bit words.
+/// The primitive integer implementations of BitString are limited by the width of the
+/// integer (at most 128 bits), so expressions with more variables than that can't be indexed by them.
+/// A BitVector has an arbitrary length and can also be used as a counter (see increment) in order
+/// to enumerate all the bitstrings of that length.
+pub struct BitVector {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVector {
+    /// Creates a new bitvector of the given length with all its bits cleared.
+    pub fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; (len + WORD_BITS - 1) / WORD_BITS],
+            len,
+        }
+    }
+
+    #[inline]
+    /// Gets the number of bits of the bitvector.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    /// Checks whether the bitvector has no bits at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Changes the length of the bitvector. New bits are cleared.
+    pub fn resize(&mut self, len: usize) {
+        self.words.resize((len + WORD_BITS - 1) / WORD_BITS, 0);
+        self.len = len;
+        self.clear_unused_bits();
+    }
+
+    /// Creates a bitvector of the given length that holds the binary representation of value.
+    /// Bits of value past the length are dropped.
+    pub fn from_u128(len: usize, value: u128) -> Self {
+        let mut bits = Self::new(len);
+        for (i, word) in bits.words.iter_mut().take(2).enumerate() {
+            *word = (value >> (i * WORD_BITS)) as u64;
+        }
+        bits.clear_unused_bits();
+        bits
+    }
+
+    /// Treats the bitvector as an unsigned number (bit 0 being the least significant bit)
+    /// and adds one to it.
+    /// Returns false if the number overflowed and wrapped around to zero, otherwise true.
+    pub fn increment(&mut self) -> bool {
+        for word in self.words.iter_mut() {
+            let (res, overflow) = word.overflowing_add(1);
+            *word = res;
+            if !overflow {
+                break;
+            }
+        }
+        let overflow = self.clear_unused_bits() || self.words.iter().all(|w| *w == 0);
+        !overflow
+    }
+
+    /// Treats the bitvector as an unsigned number and subtracts one from it.
+    /// Returns false if the number underflowed and wrapped around to all ones, otherwise true.
+    pub fn decrement(&mut self) -> bool {
+        let mut underflow = true;
+        for word in self.words.iter_mut() {
+            let (res, borrow) = word.overflowing_sub(1);
+            *word = res;
+            if !borrow {
+                underflow = false;
+                break;
+            }
+        }
+        self.clear_unused_bits();
+        !underflow
+    }
+
+    /// Gets the reflected binary Gray code of the number that the bitvector represents,
+    /// that is the number xor-ed with itself shifted right by one.
+    pub fn to_gray_code(&self) -> BitVector {
+        let mut gray = self.clone();
+        for i in 0..self.words.len() {
+            let next = self
+                .words
+                .get(i + 1)
+                .map_or(0, |word| word << (WORD_BITS - 1));
+            gray.words[i] ^= (self.words[i] >> 1) | next;
+        }
+        gray
+    }
+
+    /// Gets the positions of the bits that differ between two bitvectors of the same length.
+    pub fn differences(&self, other: &BitVector) -> Vec<usize> {
+        let mut positions = Vec::new();
+        for (i, (lhs, rhs)) in self.words.iter().zip(&other.words).enumerate() {
+            let mut diff = lhs ^ rhs;
+            while diff != 0 {
+                positions.push(i * WORD_BITS + diff.trailing_zeros() as usize);
+                diff &= diff - 1;
+            }
+        }
+        positions
+    }
+
+    /// Gets the number of cleared bits before the first set bit, or the length of the
+    /// bitvector if no bit is set.
+    pub fn trailing_zeros(&self) -> usize {
+        for (i, word) in self.words.iter().enumerate() {
+            if *word != 0 {
+                return i * WORD_BITS + word.trailing_zeros() as usize;
+            }
+        }
+        self.len
+    }
+
+    /// Gets the number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Sets every bit that is set in other (bitwise or). Both bitvectors must have the same length.
+    pub fn union_with(&mut self, other: &BitVector) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= *other;
+        }
+    }
+
+    /// Clears the bits of the last word that are past the length of the bitvector
+    /// and returns true if any of them was set.
+    fn clear_unused_bits(&mut self) -> bool {
+        let used = self.len % WORD_BITS;
+        if used == 0 {
+            return false;
+        }
+        let last = self.words.last_mut().unwrap();
+        let mask = (1u64 << used) - 1;
+        let had_unused = *last & !mask != 0;
+        *last &= mask;
+        had_unused
+    }
+}
+
+impl Ord for BitVector {
+    /// Compares the numbers that the bitvectors represent.
+    /// Shorter bitvectors are considered smaller regardless of their bits.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.len
+            .cmp(&other.len)
+            .then_with(|| self.words.iter().rev().cmp(other.words.iter().rev()))
+    }
+}
+
+impl PartialOrd for BitVector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl BitString for BitVector {
+    fn get_bit(&self, pos: usize) -> Option<u8> {
+        if pos < self.len {
+            Some(((self.words[pos / WORD_BITS] >> (pos % WORD_BITS)) & 1) as u8)
+        } else {
+            None
+        }
+    }
+
+    fn set_bit(&mut self, pos: usize) -> bool {
+        if pos < self.len {
+            self.words[pos / WORD_BITS] |= 1 << (pos % WORD_BITS);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn clear_bit(&mut self, pos: usize) -> bool {
+        if pos < self.len {
+            self.words[pos / WORD_BITS] &= !(1 << (pos % WORD_BITS));
+            true
+        } else {
+            false
+        }
+    }
+
+    fn toggle_bit(&mut self, pos: usize) -> bool {
+        if pos < self.len {
+            self.words[pos / WORD_BITS] ^= 1 << (pos % WORD_BITS);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn clear_string(&mut self) {
+        for word in self.words.iter_mut() {
+            *word = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment_carries_across_words() {
+        let mut bits = BitVector::new(130);
+        for pos in 0..64 {
+            bits.set_bit(pos);
+        }
+        assert!(bits.increment());
+        for pos in 0..64 {
+            assert_eq!(bits.get_bit(pos), Some(0));
+        }
+        assert_eq!(bits.get_bit(64), Some(1));
+    }
+
+    #[test]
+    fn test_increment_wraps_around() {
+        let mut bits = BitVector::new(3);
+        let mut count = 1;
+        while bits.increment() {
+            count += 1;
+        }
+        assert_eq!(count, 8);
+        assert_eq!(bits, BitVector::new(3));
+    }
+
+    #[test]
+    fn test_decrement_inverts_increment() {
+        let mut bits = BitVector::from_u128(100, 1 << 64);
+        assert!(bits.decrement());
+        assert_eq!(bits, BitVector::from_u128(100, (1 << 64) - 1));
+        assert!(bits.increment());
+        assert_eq!(bits, BitVector::from_u128(100, 1 << 64));
+        let mut bits = BitVector::new(5);
+        assert!(!bits.decrement());
+        assert_eq!(bits, BitVector::from_u128(5, 31));
+    }
+
+    #[test]
+    fn test_gray_code() {
+        for i in 0..300u128 {
+            let value = i << 60;
+            let gray = BitVector::from_u128(70, value).to_gray_code();
+            assert_eq!(gray, BitVector::from_u128(70, value ^ (value >> 1)));
+        }
+    }
+
+    #[test]
+    fn test_empty_bitvector() {
+        let mut bits = BitVector::new(0);
+        assert!(bits.is_empty());
+        assert_eq!(bits.get_bit(0), None);
+        assert!(!bits.increment());
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut bits = BitVector::new(200);
+        assert!(bits.set_bit(199));
+        assert!(!bits.set_bit(200));
+        assert_eq!(bits.get_bit(199), Some(1));
+        assert_eq!(bits.get_bit(200), None);
+    }
+}
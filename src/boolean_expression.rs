@@ -1,6 +1,13 @@
 use crate::bitstring_trait::*;
+use crate::expression_graph::ExpressionGraph;
+use crate::logic::Logic;
 use crate::token::*;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::{error, fmt};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 /// This represents a token of a boolean expression.
 /// These tokens are emitted by the parser which transforms identifiers into numerical ids.
@@ -51,77 +58,371 @@ impl<'source> BooleanExpression<'source> {
     /// As you can see variable A has been mapped to number 0, variable B to number 1 and variable C to number 2
     /// This is done so we can easily index a bitstring and get the value that we should assing to that variable.
     /// That was a design decision for the following reason:
-    /// When you have a boolean expression of N variables then in order to generate the truth table for that expression you must 
+    /// When you have a boolean expression of N variables then in order to generate the truth table for that expression you must
     /// generate 2^N bit strings of length N that each bit can be either 1 or 0.
     /// So essentially you want to generate all the binary strings that represent numbers 0 through 2^(N - 1).
     /// This can be easily done by having a counter starting at 0 and increasing it until it gets to 2^N and each time
-    /// use it's binary represenation to extract the values. So we have an implemenation of our BitString trait for the u32 primitive type. 
-    pub fn evaluate<T>(&self, input: T) -> u8
+    /// use it's binary represenation to extract the values. So we have an implemenation of our BitString trait for the primitive integer types
+    /// and for BitVector, which has no limit on the number of variables.
+    pub fn evaluate<T>(&self, input: &T) -> u8
     where
-        T: BitString,
+        T: BitString + ?Sized,
+    {
+        let number_of_vars = self.variable_names.len();
+        self.evaluate_with(|id| input.get_bit(number_of_vars - 1 - id as usize).unwrap())
+    }
+
+    /// Evaluates the expression by asking the given function for the value (0 or 1) of each variable id.
+    pub fn evaluate_with<F>(&self, mut value_of: F) -> u8
+    where
+        F: FnMut(u32) -> u8,
+    {
+        self.fold(|node| match node {
+            Node::Variable(id) => value_of(id),
+            Node::Constant(value) => value,
+            Node::Unary(_, value) => 1 - value,
+            Node::Binary(op, lhs, rhs) => apply_binary_operator(op, lhs, rhs),
+        })
+    }
+
+    /// Evaluates the expression for an assignment given by variable name.
+    /// Every variable of the expression must be assigned and every name of the assignment
+    /// must be a variable of the expression, otherwise an error is returned.
+    pub fn evaluate_named<S>(&self, assignment: &HashMap<S, bool>) -> Result<bool, EvaluationError>
+    where
+        S: Borrow<str> + Hash + Eq,
+    {
+        let values = self.values_by_id(assignment)?;
+        Ok(self.evaluate_with(|id| values[id as usize] as u8) == 1)
+    }
+
+    /// Converts an assignment given by variable name to a vector of values indexed by variable id.
+    /// Every variable of the expression must be assigned and every name of the assignment
+    /// must be a variable of the expression, otherwise an error is returned.
+    pub fn values_by_id<S, V>(&self, assignment: &HashMap<S, V>) -> Result<Vec<V>, EvaluationError>
+    where
+        S: Borrow<str> + Hash + Eq,
+        V: Copy,
+    {
+        if let Some(name) = assignment
+            .keys()
+            .map(|name| name.borrow())
+            .find(|name| !self.variable_names.contains(name))
+        {
+            return Err(EvaluationError::UnknownVariable(name.to_owned()));
+        }
+        let mut values = Vec::with_capacity(self.variable_names.len());
+        for name in &self.variable_names {
+            match assignment.get(*name) {
+                Some(value) => values.push(*value),
+                None => return Err(EvaluationError::MissingVariable((*name).to_owned())),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Substitutes constants for some of the variables, given by name, and simplifies the result.
+    /// The returned expression (a cofactor of this one) is over the variables that are still left,
+    /// i.e "A && B || C" with C = 0 becomes "A && B". If nothing is left the expression is a single constant.
+    /// An error is returned for names that are not variables of the expression and for results that are
+    /// too large to write out (see ExpressionGraph::to_expression).
+    pub fn restrict<S>(
+        &self,
+        assignment: &HashMap<S, bool>,
+    ) -> Result<BooleanExpression<'source>, EvaluationError>
+    where
+        S: Borrow<str> + Hash + Eq,
+    {
+        let mut graph = ExpressionGraph::new();
+        let mut substitutions = vec![None; self.variable_names.len()];
+        for (name, value) in assignment {
+            match self
+                .variable_names
+                .iter()
+                .position(|var| *var == name.borrow())
+            {
+                Some(id) => substitutions[id] = Some(graph.constant(*value as u8)),
+                None => return Err(EvaluationError::UnknownVariable(name.borrow().to_owned())),
+            }
+        }
+        let root = graph.add_expression_with(self, |graph, id| {
+            substitutions[id as usize].unwrap_or_else(|| graph.variable(id))
+        });
+        graph.to_expression(root, &self.variable_names)
+    }
+
+    /// Replaces variables, given by name, with whole expressions (F[B := X ^ Y]) and simplifies the result.
+    /// All the substitutions happen at the same time, so a replacement is never substituted into itself.
+    /// The variables of the result are the variables of this expression that were not replaced together with
+    /// the variables of the replacements, merged by name and renumbered in the order of their first appearance.
+    /// An error is returned for names that are not variables of the expression and for results that are
+    /// too large to write out (see ExpressionGraph::to_expression).
+    pub fn substitute<'a>(
+        &self,
+        substitutions: &[(&str, &BooleanExpression<'a>)],
+    ) -> Result<BooleanExpression<'a>, EvaluationError>
+    where
+        'source: 'a,
+    {
+        let mut variable_names: Vec<&'a str> = self.variable_names.clone();
+        let mut graph = ExpressionGraph::new();
+        let mut replacements = vec![None; self.variable_names.len()];
+        for (name, replacement) in substitutions {
+            let id = match self.variable_names.iter().position(|var| var == name) {
+                Some(id) => id,
+                None => return Err(EvaluationError::UnknownVariable((*name).to_owned())),
+            };
+            let ids: Vec<u32> = replacement
+                .variables()
+                .iter()
+                .map(
+                    |var| match variable_names.iter().position(|name| name == var) {
+                        Some(id) => id as u32,
+                        None => {
+                            variable_names.push(var);
+                            variable_names.len() as u32 - 1
+                        }
+                    },
+                )
+                .collect();
+            replacements[id] =
+                Some(graph.add_expression_with(replacement, |graph, id| {
+                    graph.variable(ids[id as usize])
+                }));
+        }
+        let root = graph.add_expression_with(self, |graph, id| {
+            replacements[id as usize].unwrap_or_else(|| graph.variable(id))
+        });
+        graph.to_expression(root, &variable_names)
+    }
+
+    /// Evaluates the expression in a logic other than the boolean one (see Logic),
+    /// asking the given function for the value of each variable id.
+    pub fn evaluate_in<L, F>(&self, logic: &L, mut value_of: F) -> L::Value
+    where
+        L: Logic,
+        F: FnMut(u32) -> L::Value,
+    {
+        self.fold(|node| match node {
+            Node::Variable(id) => value_of(id),
+            Node::Constant(value) => logic.constant(value),
+            Node::Unary(_, value) => logic.not(value),
+            Node::Binary(Token::AND, lhs, rhs) => logic.and(lhs, rhs),
+            Node::Binary(Token::OR, lhs, rhs) => logic.or(lhs, rhs),
+            Node::Binary(_, lhs, rhs) => logic.xor(lhs, rhs),
+        })
+    }
+
+    /// Walks the expression bottom up, the same way the evaluation does, and combines the results.
+    /// The given function is called once for every token of the expression with the results
+    /// that it returned for the operands of that token (see Node).
+    /// This is the building block of every analysis that needs the structure of the expression.
+    pub fn fold<R, F>(&self, mut f: F) -> R
+    where
+        F: FnMut(Node<R>) -> R,
     {
         let mut stack = Vec::new();
         for token in &self.exp {
-            match token {
-                BooleanExpressionToken::IDENT(id) => {
-                    stack.push(BooleanExpressionToken::RESULT(
-                        input.get_bit(self.variable_names.len() - 1 - *id as usize).unwrap(),
-                    ));
-                }
-                BooleanExpressionToken::OPERATOR(op) => match op {
-                    Token::AND => {
-                        // These are guaranted to match BooleanExpressionToken::Result(_)
-                        let lhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        let rhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        stack.push(BooleanExpressionToken::RESULT(lhs & rhs));
+            let res = match token {
+                BooleanExpressionToken::IDENT(id) => f(Node::Variable(*id)),
+                BooleanExpressionToken::RESULT(value) => f(Node::Constant(*value)),
+                BooleanExpressionToken::OPERATOR(op) => {
+                    // The parser guarantees that every operator has its operands in the stack
+                    if op.is_binary_operator() {
+                        let rhs = stack.pop().unwrap();
+                        let lhs = stack.pop().unwrap();
+                        f(Node::Binary(*op, lhs, rhs))
+                    } else {
+                        let operand = stack.pop().unwrap();
+                        f(Node::Unary(*op, operand))
                     }
-                    Token::OR => {
-                        // These are guaranted to match BooleanExpressionToken::Result(_)
-                        let lhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        let rhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        stack.push(BooleanExpressionToken::RESULT(lhs | rhs));
-                    }
-                    Token::XOR => {
-                        // These are guaranted to match BooleanExpressionToken::Result(_)
-                        let lhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        let rhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        stack.push(BooleanExpressionToken::RESULT(lhs ^ rhs));
-                    }
-                    Token::NOT => {
-                        // These are guaranted to match BooleanExpressionToken::Result(_)
-                        let lhs = match stack.pop().unwrap() {
-                            BooleanExpressionToken::RESULT(value) => value,
-                            _ => 0,
-                        };
-                        stack.push(BooleanExpressionToken::RESULT(if lhs == 0 { 1 } else { 0 }));
-                    }
-                    _ => {}
-                },
-                _ => {}
+                }
+            };
+            stack.push(res);
+        }
+        stack.pop().unwrap()
+    }
+}
+
+/// A token of a boolean expression as seen by BooleanExpression::fold.
+/// The operands of the operators are replaced by the results of folding them.
+pub enum Node<R> {
+    Variable(u32),
+    Constant(u8),
+    Unary(Token, R),
+    Binary(Token, R, R),
+}
+
+#[inline]
+/// Applies a binary operator (AND, OR or XOR) to two values that are either 0 or 1.
+pub fn apply_binary_operator(op: Token, lhs: u8, rhs: u8) -> u8 {
+    match op {
+        Token::AND => lhs & rhs,
+        Token::OR => lhs | rhs,
+        Token::XOR => lhs ^ rhs,
+        _ => unreachable!("{:?} is not a binary operator", op),
+    }
+}
+
+impl<'source> fmt::Display for BooleanExpression<'source> {
+    /// Writes the expression in infix form with only the parentheses that are needed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (text, _) = self.fold(|node| render_node(node, &self.variable_names));
+        write!(f, "{}", text)
+    }
+}
+
+/// Renders a token of an expression in infix form given the rendered operands and their precedence.
+/// The precedence is the numerical value of the operator token (the lower the tighter it binds)
+/// and 0 for variables and constants.
+/// Parentheses are added only where the precedence of the operators requires them.
+/// The binary operators are left associative, so the right hand side is also parenthesized when it has
+/// the same precedence, which keeps the structure of the expression when parsed back.
+pub fn render_node(node: Node<(String, u8)>, variable_names: &[&str]) -> (String, u8) {
+    match node {
+        Node::Variable(id) => (variable_names[id as usize].to_owned(), 0),
+        Node::Constant(value) => (value.to_string(), 0),
+        Node::Unary(_, (operand, precedence)) => {
+            if precedence > 0 {
+                (format!("!({})", operand), 0)
+            } else {
+                (format!("!{}", operand), 0)
+            }
+        }
+        Node::Binary(op, (lhs, lhs_precedence), (rhs, rhs_precedence)) => {
+            let precedence = op as u8;
+            let lhs = if lhs_precedence > precedence {
+                format!("({})", lhs)
+            } else {
+                lhs
+            };
+            let rhs = if rhs_precedence >= precedence {
+                format!("({})", rhs)
+            } else {
+                rhs
+            };
+            let symbol = match op {
+                Token::AND => "&&",
+                Token::OR => "||",
+                _ => "^",
+            };
+            (format!("{} {} {}", lhs, symbol, rhs), precedence)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// The errors that can happen when evaluating an expression by variable names.
+pub enum EvaluationError {
+    /// The assignment contains a variable that doesn't appear in the expression.
+    UnknownVariable(String),
+    /// A variable of the expression is not assigned.
+    MissingVariable(String),
+    /// The result would have more tokens than the given limit.
+    TooLarge(usize),
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationError::UnknownVariable(name) => {
+                write!(f, "Unknown variable '{}'", name)
+            }
+            EvaluationError::MissingVariable(name) => {
+                write!(f, "Variable '{}' is not assigned", name)
+            }
+            EvaluationError::TooLarge(limit) => {
+                write!(f, "The resulting expression has more than {} tokens", limit)
             }
         }
-        // This is guaranteed to be BooleanExpressionToken::RESULT(_)
-        match stack.pop().unwrap() {
-            BooleanExpressionToken::RESULT(value) => value,
-            _ => 0,
+    }
+}
+
+impl error::Error for EvaluationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use logos::Logos;
+
+    #[test]
+    fn test_evaluate_named() {
+        let exp = Parser::new(Token::lexer("A && !B")).parse().unwrap();
+        let assignment: HashMap<&str, bool> = [("A", true), ("B", false)].iter().cloned().collect();
+        assert_eq!(exp.evaluate_named(&assignment), Ok(true));
+        let assignment: HashMap<&str, bool> = [("A", true), ("B", true)].iter().cloned().collect();
+        assert_eq!(exp.evaluate_named(&assignment), Ok(false));
+    }
+
+    #[test]
+    fn test_evaluate_named_errors() {
+        let exp = Parser::new(Token::lexer("A && !B")).parse().unwrap();
+        let assignment: HashMap<String, bool> = [("A".to_owned(), true)].iter().cloned().collect();
+        assert_eq!(
+            exp.evaluate_named(&assignment),
+            Err(EvaluationError::MissingVariable("B".to_owned()))
+        );
+        let assignment: HashMap<&str, bool> = [("A", true), ("B", true), ("C", false)]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(
+            exp.evaluate_named(&assignment),
+            Err(EvaluationError::UnknownVariable("C".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_restrict() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        let restrict = |assignment: &[(&str, bool)]| {
+            let assignment: HashMap<&str, bool> = assignment.iter().cloned().collect();
+            exp.restrict(&assignment).unwrap()
+        };
+        assert_eq!(restrict(&[("C", false)]).to_string(), "A && B");
+        assert_eq!(restrict(&[("A", true)]).to_string(), "B || C");
+        assert_eq!(restrict(&[("B", false), ("C", false)]).to_string(), "0");
+        let residual = restrict(&[("A", true), ("C", false)]);
+        assert_eq!(residual.variables(), &vec!["B"]);
+        assert_eq!(residual.evaluate(&1u8), 1);
+    }
+
+    #[test]
+    fn test_substitute() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        let replacement = Parser::new(Token::lexer("X ^ A")).parse().unwrap();
+        let composed = exp.substitute(&[("B", &replacement)]).unwrap();
+        assert_eq!(composed.to_string(), "A && (X ^ A) || C");
+        assert_eq!(composed.variables(), &vec!["A", "X", "C"]);
+
+        // Substitutions happen at the same time
+        let a = Parser::new(Token::lexer("B")).parse().unwrap();
+        let b = Parser::new(Token::lexer("A")).parse().unwrap();
+        let swapped = exp.substitute(&[("A", &a), ("B", &b)]).unwrap();
+        assert_eq!(swapped.to_string(), "B && A || C");
+
+        assert_eq!(
+            exp.substitute(&[("D", &a)]),
+            Err(EvaluationError::UnknownVariable("D".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        for exp in &[
+            "A && !B || C",
+            "A && (B || C)",
+            "!(A ^ B) && A",
+            "A ^ (B ^ C)",
+            "A ^ B ^ C",
+        ] {
+            let bexp = Parser::new(Token::lexer(exp)).parse().unwrap();
+            assert_eq!(bexp.to_string(), *exp);
         }
+        let bexp = Parser::new(Token::lexer("((A) || (B && C))"))
+            .parse()
+            .unwrap();
+        assert_eq!(bexp.to_string(), "A || B && C");
     }
 }
@@ -0,0 +1,255 @@
+use crate::bit_vector::BitVector;
+use crate::bitstring_trait::*;
+use crate::boolean_expression::*;
+use crate::token::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A gate of a circuit. The operands are indices of other gates of the same circuit.
+pub enum Gate {
+    Variable(u32),
+    Constant(u8),
+    Not(usize),
+    And(usize, usize),
+    Or(usize, usize),
+    Xor(usize, usize),
+}
+
+/// A boolean expression as a graph of gates where identical subexpressions are stored once.
+/// The gates are kept in evaluation order, so every gate comes after its operands and the last
+/// gate is the whole expression.
+/// Besides the structure, the circuit keeps the value of every gate from the last evaluation.
+/// This allows re-evaluating only the gates that depend on a variable when that single variable
+/// changes (see toggle), which is what happens between consecutive rows of a Gray code enumeration.
+pub struct Circuit {
+    gates: Vec<Gate>,
+    values: Vec<u8>,
+    variable_gates: Vec<Option<usize>>,
+    dependents: Vec<Vec<usize>>,
+    // The input of the last evaluation if it was done through update
+    input: Option<BitVector>,
+}
+
+impl Circuit {
+    /// Creates the circuit of a boolean expression.
+    pub fn new(bexp: &BooleanExpression) -> Self {
+        let number_of_vars = bexp.variables().len();
+        let mut gates = Vec::new();
+        let mut index = HashMap::new();
+        bexp.fold(|node| {
+            let gate = match node {
+                Node::Variable(id) => Gate::Variable(id),
+                Node::Constant(value) => Gate::Constant(value),
+                Node::Unary(_, operand) => Gate::Not(operand),
+                Node::Binary(Token::AND, lhs, rhs) => Gate::And(lhs, rhs),
+                Node::Binary(Token::OR, lhs, rhs) => Gate::Or(lhs, rhs),
+                Node::Binary(_, lhs, rhs) => Gate::Xor(lhs, rhs),
+            };
+            *index.entry(gate).or_insert_with(|| {
+                gates.push(gate);
+                gates.len() - 1
+            })
+        });
+
+        let mut variable_gates = vec![None; number_of_vars];
+        let mut supports: Vec<BitVector> = Vec::with_capacity(gates.len());
+        for (i, gate) in gates.iter().enumerate() {
+            let mut support = BitVector::new(number_of_vars);
+            match *gate {
+                Gate::Variable(id) => {
+                    variable_gates[id as usize] = Some(i);
+                    support.set_bit(id as usize);
+                }
+                Gate::Constant(_) => {}
+                Gate::Not(operand) => support.union_with(&supports[operand]),
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    support.union_with(&supports[lhs]);
+                    support.union_with(&supports[rhs]);
+                }
+            }
+            supports.push(support);
+        }
+
+        let mut dependents = vec![Vec::new(); number_of_vars];
+        for (i, support) in supports.iter().enumerate() {
+            if let Gate::Variable(_) = gates[i] {
+                continue;
+            }
+            for (id, gate_dependents) in dependents.iter_mut().enumerate() {
+                if support.get_bit(id) == Some(1) {
+                    gate_dependents.push(i);
+                }
+            }
+        }
+
+        Self {
+            values: vec![0; gates.len()],
+            gates,
+            variable_gates,
+            dependents,
+            input: None,
+        }
+    }
+
+    #[inline]
+    /// Gets the gates of the circuit in evaluation order.
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    /// Renders every gate of the circuit in infix form using the given variable names.
+    /// The texts are in the same order as the gates.
+    pub fn render(&self, variable_names: &[&str]) -> Vec<String> {
+        let mut texts: Vec<(String, u8)> = Vec::with_capacity(self.gates.len());
+        for gate in &self.gates {
+            let node = match *gate {
+                Gate::Variable(id) => Node::Variable(id),
+                Gate::Constant(value) => Node::Constant(value),
+                Gate::Not(operand) => Node::Unary(Token::NOT, texts[operand].clone()),
+                Gate::And(lhs, rhs) => {
+                    Node::Binary(Token::AND, texts[lhs].clone(), texts[rhs].clone())
+                }
+                Gate::Or(lhs, rhs) => {
+                    Node::Binary(Token::OR, texts[lhs].clone(), texts[rhs].clone())
+                }
+                Gate::Xor(lhs, rhs) => {
+                    Node::Binary(Token::XOR, texts[lhs].clone(), texts[rhs].clone())
+                }
+            };
+            texts.push(render_node(node, variable_names));
+        }
+        texts.into_iter().map(|(text, _)| text).collect()
+    }
+
+    #[inline]
+    /// Gets the value of a gate as computed by the last evaluation.
+    pub fn value(&self, gate: usize) -> u8 {
+        self.values[gate]
+    }
+
+    #[inline]
+    /// Gets the values of all the gates, in the same order as the gates, as computed by the last evaluation.
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    #[inline]
+    /// Gets the value of the whole expression as computed by the last evaluation.
+    pub fn result(&self) -> u8 {
+        self.values[self.gates.len() - 1]
+    }
+
+    /// Evaluates every gate of the circuit for the given input and returns the result.
+    /// The input is indexed the same way as in BooleanExpression::evaluate.
+    pub fn evaluate<T>(&mut self, input: &T) -> u8
+    where
+        T: BitString + ?Sized,
+    {
+        self.input = None;
+        let number_of_vars = self.variable_gates.len();
+        for i in 0..self.gates.len() {
+            self.values[i] = match self.gates[i] {
+                Gate::Variable(id) => input.get_bit(number_of_vars - 1 - id as usize).unwrap(),
+                _ => self.compute(i),
+            };
+        }
+        self.result()
+    }
+
+    /// Evaluates the circuit for the given input, re-evaluating only the gates that depend on the
+    /// variables that changed since the previous call when that is cheaper than a full evaluation.
+    pub fn update(&mut self, input: &BitVector) -> u8 {
+        let number_of_vars = self.variable_gates.len();
+        let changed = match &self.input {
+            Some(previous) => previous.differences(input),
+            None => {
+                self.evaluate(input);
+                self.input = Some(input.clone());
+                return self.result();
+            }
+        };
+        let cost: usize = changed
+            .iter()
+            .map(|pos| self.dependents[number_of_vars - 1 - pos].len())
+            .sum();
+        if cost < self.gates.len() {
+            for pos in changed {
+                self.toggle((number_of_vars - 1 - pos) as u32);
+            }
+        } else {
+            self.evaluate(input);
+            self.input = Some(input.clone());
+        }
+        self.result()
+    }
+
+    /// Flips the value of a variable and re-evaluates only the gates that depend on it.
+    /// The circuit must have been evaluated at least once before.
+    pub fn toggle(&mut self, id: u32) -> u8 {
+        let number_of_vars = self.variable_gates.len();
+        if let Some(input) = &mut self.input {
+            input.toggle_bit(number_of_vars - 1 - id as usize);
+        }
+        if let Some(gate) = self.variable_gates[id as usize] {
+            self.values[gate] ^= 1;
+            for i in 0..self.dependents[id as usize].len() {
+                let gate = self.dependents[id as usize][i];
+                self.values[gate] = self.compute(gate);
+            }
+        }
+        self.result()
+    }
+
+    #[inline]
+    fn compute(&self, gate: usize) -> u8 {
+        match self.gates[gate] {
+            Gate::Variable(_) => self.values[gate],
+            Gate::Constant(value) => value,
+            Gate::Not(operand) => 1 - self.values[operand],
+            Gate::And(lhs, rhs) => self.values[lhs] & self.values[rhs],
+            Gate::Or(lhs, rhs) => self.values[lhs] | self.values[rhs],
+            Gate::Xor(lhs, rhs) => self.values[lhs] ^ self.values[rhs],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use logos::Logos;
+
+    #[test]
+    fn test_shared_subexpressions() {
+        let exp = Parser::new(Token::lexer("A && !B || !B && A"))
+            .parse()
+            .unwrap();
+        let circuit = Circuit::new(&exp);
+        assert_eq!(
+            circuit.gates(),
+            &[
+                Gate::Variable(0),
+                Gate::Variable(1),
+                Gate::Not(1),
+                Gate::And(0, 2),
+                Gate::And(2, 0),
+                Gate::Or(3, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_toggle_matches_full_evaluation() {
+        let exp = Parser::new(Token::lexer("(A ^ B) && !(C || A) || D"))
+            .parse()
+            .unwrap();
+        let mut circuit = Circuit::new(&exp);
+        let mut input = 0u32;
+        circuit.evaluate(&input);
+        for i in 1..16u32 {
+            let pos = i.trailing_zeros() as usize;
+            input.toggle_bit(pos);
+            assert_eq!(circuit.toggle(3 - pos as u32), exp.evaluate(&input));
+        }
+    }
+}
@@ -0,0 +1,625 @@
+use batt::bdd::VariableOrder;
+use batt::logic::{FuzzyLogic, ThreeValuedLogic};
+use batt::rows::RowOrder;
+use colored::*;
+
+pub const USAGE: &str = "\
+Usage: batt [OPTIONS] [EXPRESSION]
+       batt equiv EXPRESSION EXPRESSION
+       batt implies EXPRESSION EXPRESSION
+       batt disjoint EXPRESSION EXPRESSION
+       batt sat [OPTIONS] [EXPRESSION]
+       batt models [--cubes] [--max N] [OPTIONS] [EXPRESSION]
+       batt count [OPTIONS] [EXPRESSION]
+       batt bdd [--var-order HEURISTIC] [--sift] [OPTIONS] [EXPRESSION]
+       batt zdd [--max N] [OPTIONS] [EXPRESSION]
+       batt aig [--balance] [--aiger] [OPTIONS] [EXPRESSION]
+
+Prints the truth table of a boolean expression.
+If no expression is given it is read from the standard input.
+
+Commands:
+    equiv               Check whether two expressions have the same result for every
+                        assignment of their variables, which are matched by name.
+                        Prints a distinguishing assignment if they don't
+    implies             Check whether the second expression is true whenever the
+                        first one is
+    disjoint            Check whether the two expressions are mutually exclusive,
+                        i.e. never true at the same time
+    sat                 Find an assignment that makes the expression true with a SAT
+                        solver, which works for hundreds of variables. Exits with 3
+                        if there is none
+    models              List the satisfying assignments as they are found by the SAT
+                        solver, without scanning the truth table. With --cubes the
+                        assignments are shrunk to disjoint partial ones where the
+                        missing variables (-) don't matter. --max N stops after N
+    count               Count the satisfying assignments exactly, without scanning
+                        the truth table, and print their fraction of all the rows
+    bdd                 Build the reduced ordered binary decision diagram of the
+                        expression and print its size, its number of models and a
+                        satisfying cube. --var-order picks the order of the variables
+                        with the appearance (default), dfs or interleaved heuristic
+                        and --sift improves it by sifting the variables
+    zdd                 Build the zero-suppressed decision diagram of the family of
+                        the sets of true variables of the models, print its size
+                        next to the size of the BDD and list the sets. --max N
+                        stops after N
+    aig                 Build the and-inverter graph of the expression with
+                        structural hashing and simple rewriting and print its number
+                        of AND nodes and its depth. --balance also balances the AND
+                        trees and --aiger prints the graph in the ASCII AIGER format
+
+The commands equiv, implies and disjoint print a counterexample and exit with 3 when
+the relation doesn't hold.
+
+Options:
+    --eval A=1,B=0      Evaluate the expression for the given assignment
+    --substitute \"B := X ^ Y\"
+                        Replace a variable with another expression and work with
+                        the composed expression. Can be given more than once
+    --fix C=0,D=1       Substitute constants for some of the variables and work with
+                        the reduced expression over the remaining ones
+    --derivative NAME   Work with the boolean difference of the expression with
+                        respect to a variable, which is true where flipping the
+                        variable flips the result
+    --influence         Print how much every variable matters: its influence, its
+                        normalized Banzhaf index and its Shapley value
+    --classify          Decide whether the expression is a tautology, a contradiction
+                        or contingent and print witnesses. The exit code is 0 for
+                        tautologies, 3 for contradictions and 4 otherwise
+    --logic LOGIC       Use the three-valued kleene or lukasiewicz logic, where
+                        variables can also be X (unknown)
+    --csv FILE          Evaluate the expression for every row of a CSV file whose
+                        header names the variables
+    --order ORDER       Print the rows in binary (default) or gray code order
+    --subexpressions    Add a column for every intermediate subexpression
+    --only-true         Print only the rows where the expression is true
+    --only-false        Print only the rows where the expression is false
+    --where CONDITION   Print only the rows that satisfy another expression over
+                        the same variables, e.g. \"A && !C\"
+    --rows START..END   Print only the rows from START up to (but not including) END.
+                        Either end can be omitted
+    --sample N          Print N random rows
+    --seed SEED         The seed of the random number generator
+    --limit N           Refuse to print more than N rows. Without it every table
+                        is printed, with a warning when it has more than 65536 rows
+    --estimate N        Estimate the probability that the expression is true
+                        from N random samples
+    --probability       Compute the exact probability that the expression is true
+    --prob A=0.3,B=0.9  The probability of each variable being true (default 0.5)
+    --confidence LEVEL  The confidence level of the estimate (default 0.95)
+    --fuzzy T-NORM      Use the min, product or lukasiewicz fuzzy logic, where
+                        variables are degrees of truth in [0, 1]
+    --grid N            The number of evenly spaced values of each variable in
+                        fuzzy truth tables (default 3)
+    -h, --help          Print this message";
+
+/// The names of the commands, which are given as the first argument.
+const COMMANDS: [&str; 9] = [
+    "equiv", "implies", "disjoint", "sat", "models", "count", "bdd", "zdd", "aig",
+];
+
+#[derive(Debug, PartialEq)]
+/// A command that is run instead of printing a truth table.
+/// The commands that work on a single expression take it from Options::expression.
+pub enum Command {
+    Equiv(String, String),
+    Implies(String, String),
+    Disjoint(String, String),
+    Sat,
+    Models,
+    Count,
+    Bdd,
+    Zdd,
+    Aig,
+}
+
+impl Command {
+    /// Whether the command compares two expressions instead of working on Options::expression.
+    pub fn takes_two_expressions(&self) -> bool {
+        matches!(
+            self,
+            Command::Equiv(_, _) | Command::Implies(_, _) | Command::Disjoint(_, _)
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+/// The command line options of batt.
+pub struct Options {
+    pub command: Option<Command>,
+    pub cubes: bool,
+    pub max_models: Option<usize>,
+    pub var_order: VariableOrder,
+    pub sift: bool,
+    pub balance: bool,
+    pub aiger: bool,
+    pub expression: Option<String>,
+    pub eval: Option<Vec<(String, String)>>,
+    pub csv: Option<String>,
+    pub fix: Option<Vec<(String, bool)>>,
+    pub substitutions: Vec<(String, String)>,
+    pub derivative: Option<String>,
+    pub influence: bool,
+    pub classify: bool,
+    pub order: RowOrder,
+    pub subexpressions: bool,
+    pub only: Option<u8>,
+    pub condition: Option<String>,
+    pub rows: Option<(Option<u128>, Option<u128>)>,
+    pub sample: Option<usize>,
+    pub seed: Option<u64>,
+    pub limit: Option<u128>,
+    pub estimate: Option<u64>,
+    pub probability: bool,
+    pub probabilities: Vec<(String, f64)>,
+    pub confidence: Option<f64>,
+    pub logic: Option<ThreeValuedLogic>,
+    pub fuzzy: Option<FuzzyLogic>,
+    pub grid: Option<usize>,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parses the command line arguments (without the program name).
+    pub fn parse<I>(args: I) -> Result<Options, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter().peekable();
+        let command = match args.peek() {
+            Some(arg) if COMMANDS.contains(&arg.as_str()) => args.next(),
+            _ => None,
+        };
+        let mut positional = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--eval" => {
+                    let value = next_value(&mut args, &arg)?;
+                    // The values are parsed later since they depend on the logic
+                    options.eval = Some(parse_assignment(&value, |value| Ok(value.to_owned()))?);
+                }
+                "--substitute" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let mut parts = value.splitn(2, ":=");
+                    let name = parts.next().unwrap().trim();
+                    match parts.next() {
+                        Some(replacement) if !name.is_empty() => options
+                            .substitutions
+                            .push((name.to_owned(), replacement.trim().to_owned())),
+                        _ => {
+                            return Err(format!("Expected NAME := EXPRESSION but got '{}'", value))
+                        }
+                    }
+                }
+                "--fix" => {
+                    let value = next_value(&mut args, &arg)?;
+                    options.fix = Some(parse_assignment(&value, parse_bool)?);
+                }
+                "--derivative" => options.derivative = Some(next_value(&mut args, &arg)?),
+                "--influence" => options.influence = true,
+                "--cubes" => options.cubes = true,
+                "--max" => {
+                    let value = next_value(&mut args, &arg)?;
+                    match parse_number(&value)? {
+                        0 => {
+                            return Err(format!("Expected a positive integer but got '{}'", value))
+                        }
+                        max => options.max_models = Some(max),
+                    }
+                }
+                "--var-order" => {
+                    options.var_order = match next_value(&mut args, &arg)?.as_str() {
+                        "appearance" => VariableOrder::Appearance,
+                        "dfs" => VariableOrder::DepthFirst,
+                        "interleaved" => VariableOrder::Interleaved,
+                        heuristic => {
+                            return Err(format!("Unknown variable order heuristic '{}'", heuristic))
+                        }
+                    }
+                }
+                "--sift" => options.sift = true,
+                "--balance" => options.balance = true,
+                "--aiger" => options.aiger = true,
+                "--classify" => options.classify = true,
+                "--csv" => options.csv = Some(next_value(&mut args, &arg)?),
+                "--only-true" => options.only = Some(1),
+                "--only-false" => options.only = Some(0),
+                "--where" => options.condition = Some(next_value(&mut args, &arg)?),
+                "--rows" => options.rows = Some(parse_range(&next_value(&mut args, &arg)?)?),
+                "--sample" => options.sample = Some(parse_number(&next_value(&mut args, &arg)?)?),
+                "--seed" => options.seed = Some(parse_number(&next_value(&mut args, &arg)?)?),
+                "--limit" => options.limit = Some(parse_number(&next_value(&mut args, &arg)?)?),
+                "--estimate" => {
+                    options.estimate = Some(parse_number(&next_value(&mut args, &arg)?)?)
+                }
+                "--probability" => options.probability = true,
+                "--prob" => {
+                    let value = next_value(&mut args, &arg)?;
+                    options.probabilities = parse_assignment(&value, parse_probability)?;
+                }
+                "--confidence" => {
+                    let value = next_value(&mut args, &arg)?;
+                    match parse_probability(&value)? {
+                        confidence if confidence > 0.0 && confidence < 1.0 => {
+                            options.confidence = Some(confidence)
+                        }
+                        _ => {
+                            return Err(format!(
+                                "Expected a confidence level in (0, 1) but got '{}'",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--logic" => {
+                    options.logic = match next_value(&mut args, &arg)?.as_str() {
+                        "kleene" => Some(ThreeValuedLogic::Kleene),
+                        "lukasiewicz" => Some(ThreeValuedLogic::Lukasiewicz),
+                        logic => return Err(format!("Unknown logic '{}'", logic)),
+                    }
+                }
+                "--fuzzy" => {
+                    options.fuzzy = match next_value(&mut args, &arg)?.as_str() {
+                        "min" => Some(FuzzyLogic::Min),
+                        "product" => Some(FuzzyLogic::Product),
+                        "lukasiewicz" => Some(FuzzyLogic::Lukasiewicz),
+                        logic => return Err(format!("Unknown fuzzy logic '{}'", logic)),
+                    }
+                }
+                "--grid" => {
+                    let value = next_value(&mut args, &arg)?;
+                    match parse_number(&value)? {
+                        points if points >= 2 => options.grid = Some(points),
+                        _ => {
+                            return Err(format!(
+                                "Expected at least 2 grid points but got '{}'",
+                                value
+                            ))
+                        }
+                    }
+                }
+                "--subexpressions" => options.subexpressions = true,
+                "--order" => {
+                    options.order = match next_value(&mut args, &arg)?.as_str() {
+                        "binary" => RowOrder::Binary,
+                        "gray" => RowOrder::Gray,
+                        order => return Err(format!("Unknown row order '{}'", order)),
+                    }
+                }
+                _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+                _ => positional.push(arg),
+            }
+        }
+        let expected = match command.as_deref() {
+            Some("equiv") | Some("implies") | Some("disjoint") => 2,
+            _ => 1,
+        };
+        if let Some(arg) = positional.get(expected) {
+            return Err(format!("Unexpected argument '{}'", arg));
+        }
+        let mut positional = positional.into_iter();
+        match command {
+            Some(command) if expected == 1 => {
+                options.command = Some(match command.as_str() {
+                    "sat" => Command::Sat,
+                    "models" => Command::Models,
+                    "count" => Command::Count,
+                    "bdd" => Command::Bdd,
+                    "zdd" => Command::Zdd,
+                    _ => Command::Aig,
+                });
+                options.expression = positional.next();
+            }
+            Some(command) => {
+                let (lhs, rhs) = match (positional.next(), positional.next()) {
+                    (Some(lhs), Some(rhs)) => (lhs, rhs),
+                    _ => return Err(format!("Command '{}' expects two expressions", command)),
+                };
+                options.command = Some(match command.as_str() {
+                    "equiv" => Command::Equiv(lhs, rhs),
+                    "implies" => Command::Implies(lhs, rhs),
+                    _ => Command::Disjoint(lhs, rhs),
+                });
+            }
+            None => options.expression = positional.next(),
+        }
+        if options.cubes && options.command != Some(Command::Models) {
+            return Err("Option '--cubes' can only be used with 'models'".to_owned());
+        }
+        if options.max_models.is_some()
+            && options.command != Some(Command::Models)
+            && options.command != Some(Command::Zdd)
+        {
+            return Err("Option '--max' can only be used with 'models' and 'zdd'".to_owned());
+        }
+        if (options.balance || options.aiger) && options.command != Some(Command::Aig) {
+            return Err("Options '--balance' and '--aiger' can only be used with 'aig'".to_owned());
+        }
+        if (options.var_order != VariableOrder::Appearance || options.sift)
+            && options.command != Some(Command::Bdd)
+        {
+            return Err(
+                "Options '--var-order' and '--sift' can only be used with 'bdd'".to_owned(),
+            );
+        }
+        if options.rows.is_some() && options.sample.is_some() {
+            return Err("Options '--rows' and '--sample' can't be combined".to_owned());
+        }
+        if options.logic.is_some() && options.fuzzy.is_some() {
+            return Err("Options '--logic' and '--fuzzy' can't be combined".to_owned());
+        }
+        if (options.logic.is_some() || options.fuzzy.is_some())
+            && (options.rows.is_some()
+                || options.sample.is_some()
+                || options.condition.is_some()
+                || options.subexpressions
+                || options.order != RowOrder::Binary)
+        {
+            return Err(
+                "Three-valued and fuzzy truth tables can only be filtered with --only-true and --only-false"
+                    .to_owned(),
+            );
+        }
+        options.check_modes()?;
+        Ok(options)
+    }
+
+    /// Makes sure that at most one thing is asked for, since only one of the commands, the options that
+    /// replace the truth table and the truth table itself is printed, and that the options that shape the
+    /// truth table, pick a logic or set the probabilities are only given when they are used.
+    fn check_modes(&self) -> Result<(), String> {
+        let modes: Vec<&str> = [
+            ("--eval", self.eval.is_some()),
+            ("--classify", self.classify),
+            ("--influence", self.influence),
+            ("--probability", self.probability),
+            ("--estimate", self.estimate.is_some()),
+            ("--csv", self.csv.is_some()),
+        ]
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(option, _)| *option)
+        .collect();
+        let logics: Vec<&str> = [
+            ("--logic", self.logic.is_some()),
+            ("--fuzzy", self.fuzzy.is_some()),
+        ]
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(option, _)| *option)
+        .collect();
+        let table_options: Vec<&str> = [
+            ("--order", self.order != RowOrder::Binary),
+            ("--subexpressions", self.subexpressions),
+            ("--only-true", self.only == Some(1)),
+            ("--only-false", self.only == Some(0)),
+            ("--where", self.condition.is_some()),
+            ("--rows", self.rows.is_some()),
+            ("--sample", self.sample.is_some()),
+            ("--grid", self.grid.is_some()),
+        ]
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(option, _)| *option)
+        .collect();
+
+        if self.command.is_some() {
+            if let Some(option) = modes.iter().chain(&logics).chain(&table_options).next() {
+                return Err(format!("Option '{}' can't be used with a command", option));
+            }
+        }
+        if modes.len() > 1 {
+            return Err(format!(
+                "Options '{}' and '{}' can't be combined",
+                modes[0], modes[1]
+            ));
+        }
+        if let Some(mode) = modes.first() {
+            // --eval evaluates in the chosen logic, the other modes only work with boolean values
+            let unused = table_options
+                .iter()
+                .chain(if *mode == "--eval" { &[] } else { &logics[..] })
+                .next();
+            if let Some(option) = unused {
+                return Err(format!(
+                    "Options '{}' and '{}' can't be combined",
+                    mode, option
+                ));
+            }
+        }
+        if !self.probabilities.is_empty() && self.estimate.is_none() && !self.probability {
+            return Err(
+                "Option '--prob' can only be used with '--estimate' and '--probability'".to_owned(),
+            );
+        }
+        if self.confidence.is_some() && self.estimate.is_none() {
+            return Err("Option '--confidence' can only be used with '--estimate'".to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn next_value<I>(args: &mut I, option: &str) -> Result<String, String>
+where
+    I: Iterator<Item = String>,
+{
+    args.next()
+        .ok_or_else(|| format!("Option '{}' expects a value", option))
+}
+
+/// Parses a comma separated list of assignments such as "A=1,B=0" with the given value parser.
+pub fn parse_assignment<T, F>(value: &str, parse_value: F) -> Result<Vec<(String, T)>, String>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    value
+        .split(',')
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("Expected NAME=VALUE but got '{}'", pair))?;
+            if name.is_empty() {
+                return Err(format!("Missing variable name in '{}'", pair));
+            }
+            Ok((name.to_owned(), parse_value(value.trim())?))
+        })
+        .collect()
+}
+
+/// Parses a boolean value written as 0, 1, false or true.
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "0" | "false" => Ok(false),
+        "1" | "true" => Ok(true),
+        _ => Err(format!("Expected 0, 1, false or true but got '{}'", value)),
+    }
+}
+
+/// Parses a real number in [0, 1].
+pub fn parse_probability(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err(format!("Expected a number in [0, 1] but got '{}'", value)),
+    }
+}
+
+/// Parses a non negative integer.
+pub fn parse_number<T>(value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+{
+    value
+        .parse()
+        .map_err(|_| format!("Expected a non negative integer but got '{}'", value))
+}
+
+/// Parses a range of rows such as "4..16", "4.." or "..16".
+pub fn parse_range(value: &str) -> Result<(Option<u128>, Option<u128>), String> {
+    let mut parts = value.splitn(2, "..");
+    let start = parts.next().unwrap().trim();
+    let end = parts
+        .next()
+        .ok_or_else(|| format!("Expected START..END but got '{}'", value))?
+        .trim();
+    let start = if start.is_empty() {
+        None
+    } else {
+        Some(parse_number(start)?)
+    };
+    let end = if end.is_empty() {
+        None
+    } else {
+        Some(parse_number(end)?)
+    };
+    Ok((start, end))
+}
+
+/// Prints an error message in the same style as the parser errors.
+pub fn report_error(msg: &str) {
+    eprintln!("{}{}", "[ERROR]: ".red(), msg.red());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(str::to_owned))
+    }
+
+    /// The options that select what is printed instead of the truth table.
+    const MODES: [&str; 6] = [
+        "--eval A=1",
+        "--classify",
+        "--influence",
+        "--probability",
+        "--estimate 100",
+        "--csv rows.csv",
+    ];
+
+    /// The options that shape the truth table.
+    const TABLE_OPTIONS: [&str; 8] = [
+        "--order gray",
+        "--subexpressions",
+        "--only-true",
+        "--only-false",
+        "--where A",
+        "--rows 1..2",
+        "--sample 3",
+        "--grid 3",
+    ];
+
+    #[test]
+    fn test_parse() {
+        let options = parse("models --cubes --max 3 A||B").unwrap();
+        assert_eq!(options.command, Some(Command::Models));
+        assert!(options.cubes);
+        assert_eq!(options.max_models, Some(3));
+        assert_eq!(options.expression.as_deref(), Some("A||B"));
+        let options = parse("equiv A B").unwrap();
+        assert_eq!(
+            options.command,
+            Some(Command::Equiv("A".to_owned(), "B".to_owned()))
+        );
+        let options = parse("--order gray --subexpressions --only-true --rows 1.. A").unwrap();
+        assert_eq!(options.order, RowOrder::Gray);
+        assert_eq!(options.only, Some(1));
+        assert_eq!(options.rows, Some((Some(1), None)));
+        assert!(parse("--max 0 zdd A").is_err());
+        assert!(parse("zdd --max 0 A").is_err());
+        assert!(parse("count A B").is_err());
+        assert!(parse("--unknown A").is_err());
+    }
+
+    #[test]
+    fn test_commands_reject_modes_and_table_options() {
+        let logics = ["--logic kleene", "--fuzzy min"];
+        for option in MODES.iter().chain(&logics).chain(&TABLE_OPTIONS) {
+            let err = parse(&format!("count {} A", option)).unwrap_err();
+            assert!(err.contains("can't be used with a command"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_modes_are_exclusive() {
+        for (i, mode) in MODES.iter().enumerate() {
+            assert!(parse(&format!("{} A", mode)).is_ok(), "{}", mode);
+            for other in &MODES[i + 1..] {
+                let err = parse(&format!("{} {} A", mode, other)).unwrap_err();
+                assert!(err.contains("can't be combined"), "{}", err);
+            }
+            for option in &TABLE_OPTIONS {
+                let err = parse(&format!("{} {} A", mode, option)).unwrap_err();
+                assert!(err.contains("can't be combined"), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_logics_only_with_eval() {
+        assert!(parse("--eval A=X --logic kleene A").is_ok());
+        assert!(parse("--eval A=0.5 --fuzzy product A").is_ok());
+        assert!(parse("--logic lukasiewicz --only-true A").is_ok());
+        for mode in &MODES[1..] {
+            for logic in &["--logic kleene", "--fuzzy min"] {
+                let err = parse(&format!("{} {} A", mode, logic)).unwrap_err();
+                assert!(err.contains("can't be combined"), "{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn test_probability_options() {
+        assert!(parse("--probability --prob A=0.3 A").is_ok());
+        assert!(parse("--estimate 100 --prob A=0.3 --confidence 0.9 A").is_ok());
+        assert!(parse("--prob A=0.3 A").is_err());
+        assert!(parse("--classify --prob A=0.3 A").is_err());
+        assert!(parse("--confidence 0.9 A").is_err());
+        assert!(parse("--probability --confidence 0.9 A").is_err());
+    }
+}
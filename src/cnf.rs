@@ -0,0 +1,144 @@
+use crate::boolean_expression::BooleanExpression;
+use crate::circuit::Gate;
+use crate::expression_graph::ExpressionGraph;
+use std::collections::HashMap;
+use std::ops::Not;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A variable or its negation, stored as 2 * variable + 1 if it is negated.
+pub struct Literal(u32);
+
+impl Literal {
+    #[inline]
+    pub fn new(var: u32, negated: bool) -> Self {
+        Literal(var << 1 | negated as u32)
+    }
+
+    #[inline]
+    pub fn positive(var: u32) -> Self {
+        Self::new(var, false)
+    }
+
+    #[inline]
+    pub fn negative(var: u32) -> Self {
+        Self::new(var, true)
+    }
+
+    #[inline]
+    pub fn var(self) -> u32 {
+        self.0 >> 1
+    }
+
+    #[inline]
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    #[inline]
+    /// Gets a dense index of the literal, for tables that are indexed by literal.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Literal {
+    type Output = Literal;
+
+    #[inline]
+    fn not(self) -> Literal {
+        Literal(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A formula in conjunctive normal form, a conjunction of clauses which are disjunctions of literals.
+pub struct Cnf {
+    pub number_of_vars: usize,
+    pub clauses: Vec<Vec<Literal>>,
+}
+
+impl Cnf {
+    /// Converts an expression to an equisatisfiable CNF with the Tseitin transformation.
+    /// The first variables of the CNF are the variables of the expression with the same ids and every
+    /// AND, OR and XOR gate gets an extra variable, so the CNF grows linearly with the expression
+    /// (instead of exponentially as it does with distribution). The expression is simplified first and
+    /// identical subexpressions share their variable.
+    pub fn from_expression(bexp: &BooleanExpression) -> Self {
+        let mut graph = ExpressionGraph::new();
+        let root = graph.add_expression(bexp);
+        Self::from_graph(&graph, root, bexp.variables().len())
+    }
+
+    /// Converts a function of an expression graph to CNF (see from_expression).
+    /// The ids of the graph's variables must be smaller than number_of_vars.
+    pub fn from_graph(graph: &ExpressionGraph, root: usize, number_of_vars: usize) -> Self {
+        let mut cnf = Cnf {
+            number_of_vars,
+            clauses: Vec::new(),
+        };
+        // The operands of a gate are always created before it, so visiting the gates of the function
+        // in the order of their indices visits the operands first.
+        let mut nodes = Vec::new();
+        let mut stack = vec![root];
+        let mut literals: HashMap<usize, Literal> = HashMap::new();
+        while let Some(node) = stack.pop() {
+            if literals.contains_key(&node) {
+                continue;
+            }
+            literals.insert(node, Literal::positive(0));
+            nodes.push(node);
+            match graph.gate(node) {
+                Gate::Variable(_) | Gate::Constant(_) => {}
+                Gate::Not(operand) => stack.push(operand),
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    stack.push(lhs);
+                    stack.push(rhs);
+                }
+            }
+        }
+        nodes.sort_unstable();
+
+        for node in nodes {
+            let literal = match graph.gate(node) {
+                Gate::Variable(id) => Literal::positive(id),
+                Gate::Constant(value) => {
+                    let literal = cnf.new_var();
+                    cnf.clauses
+                        .push(vec![if value == 1 { literal } else { !literal }]);
+                    literal
+                }
+                Gate::Not(operand) => !literals[&operand],
+                Gate::And(lhs, rhs) => {
+                    let (a, b, t) = (literals[&lhs], literals[&rhs], cnf.new_var());
+                    cnf.clauses.push(vec![!t, a]);
+                    cnf.clauses.push(vec![!t, b]);
+                    cnf.clauses.push(vec![t, !a, !b]);
+                    t
+                }
+                Gate::Or(lhs, rhs) => {
+                    let (a, b, t) = (literals[&lhs], literals[&rhs], cnf.new_var());
+                    cnf.clauses.push(vec![t, !a]);
+                    cnf.clauses.push(vec![t, !b]);
+                    cnf.clauses.push(vec![!t, a, b]);
+                    t
+                }
+                Gate::Xor(lhs, rhs) => {
+                    let (a, b, t) = (literals[&lhs], literals[&rhs], cnf.new_var());
+                    cnf.clauses.push(vec![!t, a, b]);
+                    cnf.clauses.push(vec![!t, !a, !b]);
+                    cnf.clauses.push(vec![t, !a, b]);
+                    cnf.clauses.push(vec![t, a, !b]);
+                    t
+                }
+            };
+            literals.insert(node, literal);
+        }
+        cnf.clauses.push(vec![literals[&root]]);
+        cnf
+    }
+
+    fn new_var(&mut self) -> Literal {
+        self.number_of_vars += 1;
+        Literal::positive(self.number_of_vars as u32 - 1)
+    }
+}
@@ -0,0 +1,425 @@
+use crate::big_uint::BigUint;
+use crate::boolean_expression::BooleanExpression;
+use crate::circuit::Gate;
+use crate::expression_graph::ExpressionGraph;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Counts the satisfying assignments (models) of an expression exactly, without enumerating its truth table.
+/// The expression is kept on a simplifying expression graph as a conjunction of functions. The functions are
+/// split into components that don't share variables, whose counts are multiplied, and every component is
+/// split with Shannon expansion on its most frequent variable:
+/// #F = #F[x := 1] + #F[x := 0]
+/// which fixes x in every function of the component and usually breaks it into smaller components.
+/// The count of every component is cached, so components that come up again in different branches
+/// are only counted once.
+pub fn count_models(bexp: &BooleanExpression) -> BigUint {
+    let mut graph = ExpressionGraph::new();
+    let root = graph.add_expression(bexp);
+    let mut counter = ModelCounter {
+        graph,
+        supports: HashMap::new(),
+        ranges: HashMap::new(),
+        memo: HashMap::new(),
+    };
+    let (count, vars) = counter.count(vec![root]);
+    count << (bexp.variables().len() - vars)
+}
+
+/// Scales a count over some of the given number of variables to all of them, where the others are free.
+fn scale((count, count_vars): (BigUint, usize), vars: usize) -> BigUint {
+    count << (vars - count_vars)
+}
+
+/// A step of ModelCounter::count.
+enum Frame {
+    /// Counts a conjunction of functions
+    Count(Vec<usize>),
+    /// Multiplies the counts of the given number of components, and adds the given number of fixed variables
+    /// to the variables of the product
+    Product(usize, usize),
+    /// Counts a component, which is cached by its sorted functions
+    Component(Vec<usize>),
+    /// Combines the counts of the parts of a component and caches the result
+    Combine(Vec<usize>, Combination),
+}
+
+/// How the count of a component is made from the counts of its parts.
+enum Combination {
+    /// The component is the negation of its part
+    Complement,
+    /// The component is the XOR of two independent parts
+    Xor,
+    /// The parts are the cofactors of the component, for 0 and 1, on one of its given number of variables
+    Split(usize),
+}
+
+struct ModelCounter {
+    graph: ExpressionGraph,
+    supports: HashMap<usize, Rc<Vec<u32>>>,
+    ranges: HashMap<usize, Option<(u32, u32)>>,
+    // The count of every component, by its sorted functions, together with the size of its support
+    memo: HashMap<Vec<usize>, (BigUint, usize)>,
+}
+
+impl ModelCounter {
+    /// Counts the models of a conjunction of functions of the graph over the variables that they depend on
+    /// and returns the count together with the number of these variables.
+    /// A long chain of functions is split one level deeper for every link, so the conjunctions and the
+    /// components are counted with an explicit stack of frames. The frames that combine counts take the
+    /// counts of their parts from the results stack.
+    fn count(&mut self, conjuncts: Vec<usize>) -> (BigUint, usize) {
+        let mut frames = vec![Frame::Count(conjuncts)];
+        let mut results: Vec<(BigUint, usize)> = Vec::new();
+        while let Some(frame) = frames.pop() {
+            match frame {
+                Frame::Count(conjuncts) => {
+                    let (conjuncts, fixed) = match self.propagate(conjuncts) {
+                        Some(res) => res,
+                        None => {
+                            results.push((BigUint::zero(), 0));
+                            continue;
+                        }
+                    };
+                    let components = if conjuncts.is_empty() {
+                        Vec::new()
+                    } else {
+                        self.components(&conjuncts)
+                    };
+                    frames.push(Frame::Product(components.len(), fixed));
+                    frames.extend(components.into_iter().map(Frame::Component));
+                }
+                Frame::Product(components, fixed) => {
+                    let mut count = BigUint::one();
+                    let mut vars = fixed;
+                    for (component_count, component_vars) in
+                        results.drain(results.len() - components..)
+                    {
+                        count = &count * &component_count;
+                        vars += component_vars;
+                    }
+                    results.push((count, vars));
+                }
+                Frame::Component(conjuncts) => {
+                    if let Some(res) = self.memo.get(&conjuncts) {
+                        results.push(res.clone());
+                        continue;
+                    }
+                    match self.expand_component(&conjuncts) {
+                        Some((combination, parts)) => {
+                            frames.push(Frame::Combine(conjuncts, combination));
+                            frames.extend(parts.into_iter().rev().map(Frame::Count));
+                        }
+                        None => {
+                            // A single variable
+                            let res = (BigUint::one(), 1);
+                            self.memo.insert(conjuncts, res.clone());
+                            results.push(res);
+                        }
+                    }
+                }
+                Frame::Combine(conjuncts, combination) => {
+                    let res = match combination {
+                        // The variables that a part doesn't count are free in it, and so in the component
+                        Combination::Complement => {
+                            let (count, vars) = results.pop().unwrap();
+                            (&BigUint::power_of_two(vars) - &count, vars)
+                        }
+                        Combination::Xor => {
+                            let (rhs_count, rhs_vars) = results.pop().unwrap();
+                            let (lhs_count, lhs_vars) = results.pop().unwrap();
+                            let lhs_false = &BigUint::power_of_two(lhs_vars) - &lhs_count;
+                            let rhs_false = &BigUint::power_of_two(rhs_vars) - &rhs_count;
+                            (
+                                &(&lhs_count * &rhs_false) + &(&rhs_count * &lhs_false),
+                                lhs_vars + rhs_vars,
+                            )
+                        }
+                        Combination::Split(vars) => {
+                            // The cofactors may have lost more variables than x, which are free in them
+                            let high = scale(results.pop().unwrap(), vars - 1);
+                            let low = scale(results.pop().unwrap(), vars - 1);
+                            (&low + &high, vars)
+                        }
+                    };
+                    self.memo.insert(conjuncts, res.clone());
+                    results.push(res);
+                }
+            }
+        }
+        results.pop().unwrap()
+    }
+
+    /// Fixes the variables that are forced by a conjunct (unit propagation) until none of the conjuncts is a
+    /// literal. Every fixed variable is one of the variables of the conjunction, but has a single value.
+    /// Only the conjuncts that have a fixed variable are restricted, which are found through the occurrences
+    /// of the variables, so a long chain of forced variables is propagated in a single pass.
+    /// Returns the flattened conjuncts that are left and the number of fixed variables, or None if the
+    /// conjunction is false.
+    fn propagate(&mut self, conjuncts: Vec<usize>) -> Option<(Vec<usize>, usize)> {
+        let mut conjuncts = self.flatten(conjuncts)?;
+        let mut fixed = 0;
+        loop {
+            let mut units: Vec<(u32, u8)> = conjuncts
+                .iter()
+                .filter_map(|node| self.literal(*node))
+                .collect();
+            if units.is_empty() {
+                return Some((conjuncts, fixed));
+            }
+            let mut occurrences: HashMap<u32, Vec<usize>> = HashMap::new();
+            for (i, node) in conjuncts.iter().enumerate() {
+                for id in self.support(*node).iter() {
+                    occurrences.entry(*id).or_default().push(i);
+                }
+            }
+            let mut values: HashMap<u32, u8> = HashMap::new();
+            while let Some((id, value)) = units.pop() {
+                match values.insert(id, value) {
+                    Some(previous) if previous != value => return None,
+                    Some(_) => continue,
+                    None => fixed += 1,
+                }
+                for i in occurrences.get(&id).into_iter().flatten() {
+                    let restricted = self.graph.restrict(conjuncts[*i], id, value);
+                    if self.graph.constant_value(restricted) == Some(0) {
+                        return None;
+                    }
+                    if let Some(unit) = self.literal(restricted) {
+                        units.push(unit);
+                    }
+                    conjuncts[*i] = restricted;
+                }
+            }
+            // Restricting may have turned conjuncts into ANDs, which can have more literals
+            conjuncts = self.flatten(conjuncts)?;
+        }
+    }
+
+    /// Chooses how to count a conjunction of functions that can't be split into independent components
+    /// and returns the conjunctions to count for it, in the order that the combination takes them,
+    /// or None if the component is a single variable.
+    fn expand_component(&mut self, conjuncts: &[usize]) -> Option<(Combination, Vec<Vec<usize>>)> {
+        let expansion = match (conjuncts.len(), self.graph.gate(conjuncts[0])) {
+            (1, Gate::Variable(_)) => return None,
+            (1, Gate::Not(operand)) => (Combination::Complement, vec![vec![operand]]),
+            (1, Gate::Or(_, _)) => {
+                // The models of a disjunction are the assignments that don't satisfy the conjunction
+                // of the negations of its operands, which flatten splits
+                let negation = self.graph.not(conjuncts[0]);
+                (Combination::Complement, vec![vec![negation]])
+            }
+            (1, Gate::Xor(lhs, rhs)) if self.are_independent(lhs, rhs) => {
+                (Combination::Xor, vec![vec![lhs], vec![rhs]])
+            }
+            _ => {
+                let mut occurrences: HashMap<u32, usize> = HashMap::new();
+                for node in conjuncts {
+                    for id in self.support(*node).iter() {
+                        *occurrences.entry(*id).or_insert(0) += 1;
+                    }
+                }
+                let vars = occurrences.len();
+                // Split on the most frequent variable. Ties go to the middle one of the tied variables,
+                // which cuts chains of conjuncts in half instead of shortening them by one
+                let most = *occurrences.values().max().unwrap();
+                let mut candidates: Vec<u32> = occurrences
+                    .iter()
+                    .filter(|(_, occurrences)| **occurrences == most)
+                    .map(|(id, _)| *id)
+                    .collect();
+                candidates.sort_unstable();
+                let id = candidates[candidates.len() / 2];
+                let mut cofactors = Vec::with_capacity(2);
+                for value in [0, 1] {
+                    // Only the conjuncts that have the variable change
+                    let mut cofactor = conjuncts.to_vec();
+                    for node in cofactor.iter_mut() {
+                        if self.support(*node).binary_search(&id).is_ok() {
+                            *node = self.graph.restrict(*node, id, value);
+                        }
+                    }
+                    cofactors.push(cofactor);
+                }
+                (Combination::Split(vars), cofactors)
+            }
+        };
+        Some(expansion)
+    }
+
+    /// Gets the variable and the value that a function forces if it is a single variable or its negation.
+    fn literal(&self, node: usize) -> Option<(u32, u8)> {
+        match self.graph.gate(node) {
+            Gate::Variable(id) => Some((id, 1)),
+            Gate::Not(operand) => match self.graph.gate(operand) {
+                Gate::Variable(id) => Some((id, 0)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Splits the ANDs of a conjunction, and the negated ORs as ANDs of the negations, into their operands
+    /// and drops the functions that are true.
+    /// Returns the functions sorted, or None if one of them is false.
+    fn flatten(&mut self, conjuncts: Vec<usize>) -> Option<Vec<usize>> {
+        let mut stack = conjuncts;
+        let mut flat = Vec::new();
+        while let Some(node) = stack.pop() {
+            match self.graph.gate(node) {
+                Gate::Constant(0) => return None,
+                Gate::Constant(_) => {}
+                Gate::And(lhs, rhs) => {
+                    stack.push(lhs);
+                    stack.push(rhs);
+                }
+                Gate::Not(operand) => match self.graph.gate(operand) {
+                    Gate::Or(lhs, rhs) => {
+                        let (lhs, rhs) = (self.graph.not(lhs), self.graph.not(rhs));
+                        stack.push(lhs);
+                        stack.push(rhs);
+                    }
+                    _ => flat.push(node),
+                },
+                _ => flat.push(node),
+            }
+        }
+        flat.sort_unstable();
+        flat.dedup();
+        Some(flat)
+    }
+
+    /// Groups the functions of a conjunction into components that share variables.
+    fn components(&mut self, conjuncts: &[usize]) -> Vec<Vec<usize>> {
+        if conjuncts.len() == 1 {
+            return vec![conjuncts.to_vec()];
+        }
+        let mut parents: Vec<usize> = (0..conjuncts.len()).collect();
+        fn find(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        let mut owners: HashMap<u32, usize> = HashMap::new();
+        for (i, node) in conjuncts.iter().enumerate() {
+            for id in self.support(*node).iter() {
+                match owners.get(id) {
+                    Some(owner) => {
+                        let (a, b) = (find(&mut parents, i), find(&mut parents, *owner));
+                        parents[a] = b;
+                    }
+                    None => {
+                        owners.insert(*id, i);
+                    }
+                }
+            }
+        }
+        let mut components: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, node) in conjuncts.iter().enumerate() {
+            let root = find(&mut parents, i);
+            components.entry(root).or_default().push(*node);
+        }
+        components.into_values().collect()
+    }
+
+    fn support(&mut self, node: usize) -> Rc<Vec<u32>> {
+        let graph = &self.graph;
+        self.supports
+            .entry(node)
+            .or_insert_with(|| Rc::new(graph.support(node)))
+            .clone()
+    }
+
+    fn are_independent(&mut self, lhs: usize, rhs: usize) -> bool {
+        // Operands whose ranges don't overlap are independent, which avoids the supports of long chains
+        let lhs_range = self.graph.range(lhs, &mut self.ranges);
+        let rhs_range = self.graph.range(rhs, &mut self.ranges);
+        if let (Some((lhs_min, lhs_max)), Some((rhs_min, rhs_max))) = (lhs_range, rhs_range) {
+            if lhs_max < rhs_min || rhs_max < lhs_min {
+                return true;
+            }
+        }
+        let lhs = self.support(lhs);
+        let rhs = self.support(rhs);
+        let (mut i, mut j) = (0, 0);
+        while i < lhs.len() && j < rhs.len() {
+            match lhs[i].cmp(&rhs[j]) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::rows::{RowOrder, Rows};
+    use crate::test_util::variable_name;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_count_matches_enumeration() {
+        let exp = Parser::new(Token::lexer(
+            "(A ^ B) && !(C || A) || D && (B || !E) ^ C || F && !F",
+        ))
+        .parse()
+        .unwrap();
+        let expected = Rows::new(&exp, RowOrder::Binary).with_result(1).count();
+        assert_eq!(count_models(&exp), BigUint::from(expected as u64));
+        // Fixing A makes B disappear from the operand of the negation
+        let exp = Parser::new(Token::lexer("!(A && (A || B)) || C && !C"))
+            .parse()
+            .unwrap();
+        assert_eq!(count_models(&exp), BigUint::from(4));
+    }
+
+    #[test]
+    fn test_count_of_many_variables() {
+        let clauses: Vec<String> = (0..100)
+            .map(|i| format!("({} || {})", variable_name(2 * i), variable_name(2 * i + 1)))
+            .collect();
+        let exp = clauses.join(" && ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        // 3 of the 4 assignments of every clause
+        let mut expected = BigUint::one();
+        for _ in 0..100 {
+            expected = &expected * &BigUint::from(3);
+        }
+        assert_eq!(count_models(&exp), expected);
+    }
+
+    #[test]
+    fn test_count_of_long_chain() {
+        // Every variable implies the next one, so the models are the n + 2
+        // ways of switching from false to true somewhere along the chain
+        let n = 5_000;
+        let clauses: Vec<String> = (0..n)
+            .map(|i| format!("(!{} || {})", variable_name(i), variable_name(i + 1)))
+            .collect();
+        let exp = clauses.join(" && ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        assert_eq!(count_models(&exp), BigUint::from(n as u64 + 2));
+    }
+
+    #[test]
+    fn test_count_of_deep_xor_chain() {
+        // Every term has 3 models out of 4, so the models of the XOR of n terms are the assignments
+        // where an odd number of terms is true: ((3 + 1)^n - (1 - 3)^n) / 2 = 2^(2n - 1) - 2^(n - 1) for even n
+        let n = 10_000;
+        let terms: Vec<String> = (0..n)
+            .map(|i| format!("({} || {})", variable_name(2 * i), variable_name(2 * i + 1)))
+            .collect();
+        let exp = terms.join(" ^ ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        let expected = &BigUint::power_of_two(2 * n - 1) - &BigUint::power_of_two(n - 1);
+        assert_eq!(count_models(&exp), expected);
+    }
+}
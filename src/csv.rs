@@ -0,0 +1,283 @@
+use crate::boolean_expression::BooleanExpression;
+use std::io::{self, BufRead, Write};
+use std::{error, fmt};
+
+#[derive(Debug, PartialEq)]
+/// The outcome of evaluating an expression over the rows of a CSV file.
+pub struct CsvSummary {
+    pub rows: usize,
+    pub true_rows: usize,
+}
+
+#[derive(Debug)]
+/// The errors that can happen when evaluating an expression over a CSV file.
+pub enum CsvError {
+    Io(io::Error),
+    /// The input doesn't even have a header row.
+    MissingHeader,
+    /// A variable of the expression has no column in the header.
+    MissingColumn(String),
+    /// A row doesn't have as many fields as the header.
+    FieldCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A quoted field is not closed before the end of the input.
+    UnterminatedQuote {
+        line: usize,
+    },
+    /// A field used by the expression is not a boolean value.
+    InvalidValue {
+        line: usize,
+        value: String,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Io(err) => write!(f, "{}", err),
+            CsvError::MissingHeader => write!(f, "The CSV input has no header row"),
+            CsvError::MissingColumn(name) => {
+                write!(f, "There is no column for variable '{}'", name)
+            }
+            CsvError::FieldCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {} has {} fields but the header has {}",
+                line, found, expected
+            ),
+            CsvError::UnterminatedQuote { line } => {
+                write!(f, "The quoted field of line {} is never closed", line)
+            }
+            CsvError::InvalidValue { line, value } => write!(
+                f,
+                "Line {}: expected 0, 1, false or true but got '{}'",
+                line, value
+            ),
+        }
+    }
+}
+
+impl error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(err: io::Error) -> Self {
+        CsvError::Io(err)
+    }
+}
+
+/// Evaluates the expression for every row of a CSV input.
+/// The first row of the input is the header, and the columns are mapped to the
+/// variables of the expression by name. Columns that are not variables of the expression are ignored.
+/// Fields can be quoted as in RFC 4180 (see split_fields) and every row must have as many fields as the header.
+/// Every row is written back unchanged with an extra column that holds the result,
+/// and the header gets an extra column named after `result_name`.
+pub fn evaluate_csv<R, W>(
+    bexp: &BooleanExpression,
+    result_name: &str,
+    reader: R,
+    mut writer: W,
+) -> Result<CsvSummary, CsvError>
+where
+    R: BufRead,
+    W: Write,
+{
+    let mut lines = reader.lines();
+    let mut line_number = 0;
+    let (header, columns) = match read_record(&mut lines, &mut line_number)? {
+        Some((_, header, columns)) => (header, columns),
+        None => return Err(CsvError::MissingHeader),
+    };
+    let mut variable_columns = Vec::with_capacity(bexp.variables().len());
+    for var in bexp.variables() {
+        match columns.iter().position(|column| column == var) {
+            Some(index) => variable_columns.push(index),
+            None => return Err(CsvError::MissingColumn((*var).to_owned())),
+        }
+    }
+    writeln!(writer, "{},{}", header, quote_field(result_name))?;
+
+    let mut summary = CsvSummary {
+        rows: 0,
+        true_rows: 0,
+    };
+    let mut values = vec![0; variable_columns.len()];
+    while let Some((line, record, fields)) = read_record(&mut lines, &mut line_number)? {
+        if record.trim().is_empty() {
+            continue;
+        }
+        if fields.len() != columns.len() {
+            return Err(CsvError::FieldCount {
+                line,
+                expected: columns.len(),
+                found: fields.len(),
+            });
+        }
+        for (value, index) in values.iter_mut().zip(&variable_columns) {
+            let field = &fields[*index];
+            *value = parse_value(field).ok_or_else(|| CsvError::InvalidValue {
+                line,
+                value: field.clone(),
+            })?;
+        }
+        let res = bexp.evaluate_with(|id| values[id as usize]);
+        writeln!(writer, "{},{}", record, res)?;
+        summary.rows += 1;
+        summary.true_rows += res as usize;
+    }
+    Ok(summary)
+}
+
+/// Reads the next record of the input, which continues on the next lines while a quoted field is open.
+/// Returns the number of the line where the record starts, the text of the record and its fields.
+fn read_record<I>(
+    lines: &mut I,
+    line_number: &mut usize,
+) -> Result<Option<(usize, String, Vec<String>)>, CsvError>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut record = match lines.next() {
+        Some(line) => line?,
+        None => return Ok(None),
+    };
+    *line_number += 1;
+    let start = *line_number;
+    loop {
+        if let Some(fields) = split_fields(&record) {
+            return Ok(Some((start, record, fields)));
+        }
+        match lines.next() {
+            Some(line) => {
+                record.push('\n');
+                record.push_str(&line?);
+                *line_number += 1;
+            }
+            None => return Err(CsvError::UnterminatedQuote { line: start }),
+        }
+    }
+}
+
+/// Splits a record into its fields as in RFC 4180. A field that starts with a double quote ends at the
+/// next lone double quote, so it can contain commas and line breaks, and two double quotes in it stand
+/// for one. The spaces around the fields are ignored.
+/// Returns None if the record ends inside a quoted field.
+fn split_fields(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut chars = record.chars().peekable();
+    loop {
+        while matches!(chars.peek(), Some(c) if *c != ',' && c.is_whitespace()) {
+            chars.next();
+        }
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return None,
+                }
+            }
+            while matches!(chars.peek(), Some(c) if *c != ',') {
+                chars.next();
+            }
+        } else {
+            while let Some(c) = chars.peek().filter(|c| **c != ',') {
+                field.push(*c);
+                chars.next();
+            }
+            field.truncate(field.trim_end().len());
+        }
+        fields.push(field);
+        if chars.next().is_none() {
+            return Some(fields);
+        }
+    }
+}
+
+/// Quotes a field if it has characters that need it.
+fn quote_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn parse_value(field: &str) -> Option<u8> {
+    match field.to_ascii_lowercase().as_str() {
+        "0" | "false" => Some(0),
+        "1" | "true" => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_evaluate_csv() {
+        let exp = Parser::new(Token::lexer("req && !ack")).parse().unwrap();
+        let input = "time,ack,req\n0,0,1\n1,1,1\n2,false,true\n";
+        let mut output = Vec::new();
+        let summary = evaluate_csv(&exp, "req && !ack", input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "time,ack,req,req && !ack\n0,0,1,1\n1,1,1,0\n2,false,true,1\n"
+        );
+        assert_eq!(
+            summary,
+            CsvSummary {
+                rows: 3,
+                true_rows: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        let exp = Parser::new(Token::lexer("a && b")).parse().unwrap();
+        let input = "note,\"a\", b\n\"x, \"\"y\"\"\",1,1\n\"two\nlines\",1,0\n";
+        let mut output = Vec::new();
+        let summary = evaluate_csv(&exp, "a, b", input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "note,\"a\", b,\"a, b\"\n\"x, \"\"y\"\"\",1,1,1\n\"two\nlines\",1,0,0\n"
+        );
+        assert_eq!(summary.rows, 2);
+        // An unquoted comma adds a field, which is an error instead of shifting the columns
+        let input = "note,a,b\nx, y,1,1\n";
+        match evaluate_csv(&exp, "", input.as_bytes(), Vec::new()) {
+            Err(CsvError::FieldCount {
+                line: 2,
+                expected: 3,
+                found: 4,
+            }) => {}
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_csv_missing_column() {
+        let exp = Parser::new(Token::lexer("req && !ack")).parse().unwrap();
+        let input = "time,req\n0,1\n";
+        match evaluate_csv(&exp, "", input.as_bytes(), Vec::new()) {
+            Err(CsvError::MissingColumn(name)) => assert_eq!(name, "ack"),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::boolean_expression::BooleanExpression;
+use crate::cnf::Cnf;
+use crate::expression_graph::ExpressionGraph;
+use crate::sat;
+
+#[derive(Debug, Clone, PartialEq)]
+/// The classification of an expression by its possible results, together with witnesses.
+/// The witnesses are assignments of the expression's variables, indexed by id.
+pub enum Classification {
+    /// The expression is true for every assignment.
+    Tautology { satisfying: Vec<u8> },
+    /// The expression is false for every assignment.
+    Contradiction { falsifying: Vec<u8> },
+    /// The expression is true for some assignments and false for others.
+    Contingency {
+        satisfying: Vec<u8>,
+        falsifying: Vec<u8>,
+    },
+}
+
+impl Classification {
+    /// Gets an assignment that makes the expression true, unless it is a contradiction.
+    pub fn satisfying(&self) -> Option<&[u8]> {
+        match self {
+            Classification::Tautology { satisfying }
+            | Classification::Contingency { satisfying, .. } => Some(satisfying),
+            Classification::Contradiction { .. } => None,
+        }
+    }
+
+    /// Gets an assignment that makes the expression false, unless it is a tautology.
+    pub fn falsifying(&self) -> Option<&[u8]> {
+        match self {
+            Classification::Contradiction { falsifying }
+            | Classification::Contingency { falsifying, .. } => Some(falsifying),
+            Classification::Tautology { .. } => None,
+        }
+    }
+}
+
+/// Decides whether an expression is a tautology, a contradiction or contingent.
+pub fn classify(bexp: &BooleanExpression) -> Classification {
+    let satisfying = find_assignment(bexp, 1);
+    let falsifying = find_assignment(bexp, 0);
+    match (satisfying, falsifying) {
+        (Some(satisfying), Some(falsifying)) => Classification::Contingency {
+            satisfying,
+            falsifying,
+        },
+        (Some(satisfying), None) => Classification::Tautology { satisfying },
+        (None, Some(falsifying)) => Classification::Contradiction { falsifying },
+        (None, None) => unreachable!("An expression is either true or false for any assignment"),
+    }
+}
+
+/// Finds an assignment of the variables, indexed by id, for which the expression evaluates to the given result,
+/// or None if there isn't one.
+/// The expression is converted to CNF and handed to the SAT solver, so this works for expressions with
+/// far too many variables to enumerate their truth table.
+pub fn find_assignment(bexp: &BooleanExpression, result: u8) -> Option<Vec<u8>> {
+    let mut graph = ExpressionGraph::new();
+    let mut root = graph.add_expression(bexp);
+    if result == 0 {
+        root = graph.not(root);
+    }
+    satisfy(graph, root, bexp.variables().len())
+}
+
+/// Finds an assignment of the graph's variables that makes a function of the graph true (see find_assignment).
+fn satisfy(graph: ExpressionGraph, node: usize, number_of_vars: usize) -> Option<Vec<u8>> {
+    let cnf = Cnf::from_graph(&graph, node, number_of_vars);
+    sat::solve_cnf(&cnf, number_of_vars).model
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An assignment for which a relation between two expressions doesn't hold, with the results of both expressions.
+pub struct Counterexample {
+    /// The values of the merged variables of the two expressions, indexed as in Relation::variables.
+    pub assignment: Vec<u8>,
+    pub lhs: u8,
+    pub rhs: u8,
+}
+
+#[derive(Debug)]
+/// The outcome of checking a relation between two expressions.
+pub struct Relation<'source> {
+    /// The variables of both expressions merged by name: the variables of the left hand side in their order,
+    /// followed by the variables that only the right hand side has.
+    pub variables: Vec<&'source str>,
+    /// An assignment that violates the relation, or None if the relation holds.
+    pub counterexample: Option<Counterexample>,
+}
+
+impl<'source> Relation<'source> {
+    #[inline]
+    pub fn holds(&self) -> bool {
+        self.counterexample.is_none()
+    }
+}
+
+/// Checks whether two expressions have the same result for every assignment.
+/// The variables of the expressions are matched by name, since the parser numbers them independently.
+pub fn equivalence<'source>(
+    lhs: &BooleanExpression<'source>,
+    rhs: &BooleanExpression<'source>,
+) -> Relation<'source> {
+    check_relation(lhs, rhs, |graph, lhs, rhs| graph.xor(lhs, rhs))
+}
+
+/// Checks whether the left hand side implies (entails) the right hand side,
+/// which means that the right hand side is true for every assignment that makes the left hand side true.
+pub fn entailment<'source>(
+    lhs: &BooleanExpression<'source>,
+    rhs: &BooleanExpression<'source>,
+) -> Relation<'source> {
+    check_relation(lhs, rhs, |graph, lhs, rhs| {
+        let rhs = graph.not(rhs);
+        graph.and(lhs, rhs)
+    })
+}
+
+/// Checks whether two expressions are mutually exclusive, which means that no assignment makes both of them true.
+pub fn mutual_exclusion<'source>(
+    lhs: &BooleanExpression<'source>,
+    rhs: &BooleanExpression<'source>,
+) -> Relation<'source> {
+    check_relation(lhs, rhs, |graph, lhs, rhs| graph.and(lhs, rhs))
+}
+
+/// Adds two expressions to a graph with their variables merged by name and searches for an assignment
+/// that makes the violation of a relation, built by the given function out of the two expressions, true.
+fn check_relation<'source, F>(
+    lhs: &BooleanExpression<'source>,
+    rhs: &BooleanExpression<'source>,
+    violation: F,
+) -> Relation<'source>
+where
+    F: FnOnce(&mut ExpressionGraph, usize, usize) -> usize,
+{
+    let mut variables: Vec<&'source str> = lhs.variables().to_vec();
+    let ids: Vec<u32> = rhs
+        .variables()
+        .iter()
+        .map(|var| match variables.iter().position(|name| name == var) {
+            Some(id) => id as u32,
+            None => {
+                variables.push(var);
+                variables.len() as u32 - 1
+            }
+        })
+        .collect();
+    let mut graph = ExpressionGraph::new();
+    let lhs_node = graph.add_expression(lhs);
+    let rhs_node = graph.add_expression_with(rhs, |graph, id| graph.variable(ids[id as usize]));
+    let root = violation(&mut graph, lhs_node, rhs_node);
+    let counterexample = satisfy(graph, root, variables.len()).map(|assignment| Counterexample {
+        lhs: lhs.evaluate_with(|id| assignment[id as usize]),
+        rhs: rhs.evaluate_with(|id| assignment[ids[id as usize] as usize]),
+        assignment,
+    });
+    Relation {
+        variables,
+        counterexample,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_classify() {
+        let exp = Parser::new(Token::lexer("A || !A && B || !B"))
+            .parse()
+            .unwrap();
+        assert!(matches!(classify(&exp), Classification::Tautology { .. }));
+        let exp = Parser::new(Token::lexer("(A ^ B) && (A ^ !B)"))
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            classify(&exp),
+            Classification::Contradiction { .. }
+        ));
+        let exp = Parser::new(Token::lexer("A && !B || C")).parse().unwrap();
+        let classification = classify(&exp);
+        let satisfying = classification.satisfying().unwrap();
+        let falsifying = classification.falsifying().unwrap();
+        assert_eq!(exp.evaluate_with(|id| satisfying[id as usize]), 1);
+        assert_eq!(exp.evaluate_with(|id| falsifying[id as usize]), 0);
+    }
+
+    #[test]
+    fn test_equivalence_merges_variables_by_name() {
+        let lhs = Parser::new(Token::lexer("!(A && B) || C")).parse().unwrap();
+        let rhs = Parser::new(Token::lexer("C || !B || !A")).parse().unwrap();
+        let relation = equivalence(&lhs, &rhs);
+        assert!(relation.holds());
+        assert_eq!(relation.variables, vec!["A", "B", "C"]);
+
+        let rhs = Parser::new(Token::lexer("D || !B || !A")).parse().unwrap();
+        let relation = equivalence(&lhs, &rhs);
+        assert_eq!(relation.variables, vec!["A", "B", "C", "D"]);
+        let counterexample = relation.counterexample.unwrap();
+        assert_ne!(counterexample.lhs, counterexample.rhs);
+        let values = &counterexample.assignment;
+        assert_ne!(values[2], values[3]);
+        assert_eq!((values[0], values[1]), (1, 1));
+    }
+
+    #[test]
+    fn test_entailment_and_mutual_exclusion() {
+        let guard = Parser::new(Token::lexer("A && B")).parse().unwrap();
+        let cover = Parser::new(Token::lexer("B || C")).parse().unwrap();
+        assert!(entailment(&guard, &cover).holds());
+        let counterexample = entailment(&cover, &guard).counterexample.unwrap();
+        assert_eq!((counterexample.lhs, counterexample.rhs), (1, 0));
+
+        let other = Parser::new(Token::lexer("!B && C")).parse().unwrap();
+        assert!(mutual_exclusion(&guard, &other).holds());
+        let counterexample = mutual_exclusion(&cover, &other).counterexample.unwrap();
+        assert_eq!((counterexample.lhs, counterexample.rhs), (1, 1));
+    }
+}
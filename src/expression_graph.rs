@@ -0,0 +1,349 @@
+use crate::boolean_expression::*;
+use crate::circuit::Gate;
+use crate::token::*;
+use std::collections::HashMap;
+
+/// The largest number of tokens of an expression that ExpressionGraph::to_expression writes.
+pub const MAX_EXPRESSION_TOKENS: usize = 1 << 24;
+
+/// A graph of boolean functions built out of gates, where identical gates are stored once (hash consing)
+/// and constants are propagated while the gates are created.
+/// Unlike a Circuit, which keeps the exact structure of an expression for evaluation, the graph simplifies
+/// the functions that it builds (i.e "A && 1" becomes "A" and "A ^ A" becomes "0"), so it is used by the
+/// analyses that transform expressions, like substituting constants for some of the variables.
+/// A function of the graph is identified by the index of its top gate.
+#[derive(Default)]
+pub struct ExpressionGraph {
+    gates: Vec<Gate>,
+    index: HashMap<Gate, usize>,
+}
+
+impl ExpressionGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    /// Gets a gate of the graph.
+    pub fn gate(&self, node: usize) -> Gate {
+        self.gates[node]
+    }
+
+    /// Gets the constant value of a node or None if the node is not a constant.
+    pub fn constant_value(&self, node: usize) -> Option<u8> {
+        match self.gates[node] {
+            Gate::Constant(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn constant(&mut self, value: u8) -> usize {
+        self.add(Gate::Constant(value))
+    }
+
+    pub fn variable(&mut self, id: u32) -> usize {
+        self.add(Gate::Variable(id))
+    }
+
+    pub fn not(&mut self, operand: usize) -> usize {
+        match self.gates[operand] {
+            Gate::Constant(value) => self.constant(1 - value),
+            Gate::Not(inner) => inner,
+            _ => self.add(Gate::Not(operand)),
+        }
+    }
+
+    pub fn and(&mut self, lhs: usize, rhs: usize) -> usize {
+        match (self.constant_value(lhs), self.constant_value(rhs)) {
+            (Some(0), _) | (_, Some(1)) => lhs,
+            (_, Some(0)) | (Some(1), _) => rhs,
+            _ if lhs == rhs => lhs,
+            _ if self.are_complements(lhs, rhs) => self.constant(0),
+            _ => self.add(Gate::And(lhs, rhs)),
+        }
+    }
+
+    pub fn or(&mut self, lhs: usize, rhs: usize) -> usize {
+        match (self.constant_value(lhs), self.constant_value(rhs)) {
+            (Some(1), _) | (_, Some(0)) => lhs,
+            (_, Some(1)) | (Some(0), _) => rhs,
+            _ if lhs == rhs => lhs,
+            _ if self.are_complements(lhs, rhs) => self.constant(1),
+            _ => self.add(Gate::Or(lhs, rhs)),
+        }
+    }
+
+    pub fn xor(&mut self, lhs: usize, rhs: usize) -> usize {
+        match (self.constant_value(lhs), self.constant_value(rhs)) {
+            (Some(0), _) => rhs,
+            (_, Some(0)) => lhs,
+            (Some(1), _) => self.not(rhs),
+            (_, Some(1)) => self.not(lhs),
+            _ if lhs == rhs => self.constant(0),
+            _ if self.are_complements(lhs, rhs) => self.constant(1),
+            _ => self.add(Gate::Xor(lhs, rhs)),
+        }
+    }
+
+    /// Applies an operator token to nodes of the graph. The rhs is ignored for NOT.
+    pub fn apply(&mut self, op: Token, lhs: usize, rhs: usize) -> usize {
+        match op {
+            Token::AND => self.and(lhs, rhs),
+            Token::OR => self.or(lhs, rhs),
+            Token::XOR => self.xor(lhs, rhs),
+            _ => self.not(lhs),
+        }
+    }
+
+    /// Adds a boolean expression to the graph and returns its node.
+    /// The variables of the graph are the variable ids of the expression.
+    pub fn add_expression(&mut self, bexp: &BooleanExpression) -> usize {
+        self.add_expression_with(bexp, |graph, id| graph.variable(id))
+    }
+
+    /// Adds a boolean expression to the graph replacing every variable with the node returned by
+    /// the given function for its id.
+    pub fn add_expression_with<F>(&mut self, bexp: &BooleanExpression, mut variable: F) -> usize
+    where
+        F: FnMut(&mut Self, u32) -> usize,
+    {
+        bexp.fold(|node| match node {
+            Node::Variable(id) => variable(self, id),
+            Node::Constant(value) => self.constant(value),
+            Node::Unary(op, operand) => self.apply(op, operand, operand),
+            Node::Binary(op, lhs, rhs) => self.apply(op, lhs, rhs),
+        })
+    }
+
+    /// Replaces the variables of a function by nodes, given by variable id, and simplifies the result.
+    /// Variables mapped to None are kept as they are.
+    pub fn substitute(&mut self, node: usize, substitutions: &[Option<usize>]) -> usize {
+        self.substitute_with(node, |id| substitutions.get(id as usize).cloned().flatten())
+    }
+
+    /// Substitutes a constant for a single variable (a cofactor of the function).
+    pub fn restrict(&mut self, node: usize, id: u32, value: u8) -> usize {
+        let constant = self.constant(value);
+        self.substitute_with(node, |var| if var == id { Some(constant) } else { None })
+    }
+
+    /// Same as substitute, but asks the given function for the replacement of every variable.
+    /// The gates are rebuilt with an explicit stack, since the graph can be very deep.
+    fn substitute_with<F>(&mut self, node: usize, substitution: F) -> usize
+    where
+        F: Fn(u32) -> Option<usize>,
+    {
+        let mut memo = HashMap::new();
+        // A gate is pushed again above its operands and rebuilt once they have been substituted
+        let mut stack = vec![(node, false)];
+        while let Some((top, operands_done)) = stack.pop() {
+            if memo.contains_key(&top) {
+                continue;
+            }
+            let res = match self.gates[top] {
+                Gate::Variable(id) => substitution(id).unwrap_or(top),
+                Gate::Constant(_) => top,
+                Gate::Not(operand) if !operands_done => {
+                    stack.push((top, true));
+                    stack.push((operand, false));
+                    continue;
+                }
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs)
+                    if !operands_done =>
+                {
+                    stack.push((top, true));
+                    stack.push((rhs, false));
+                    stack.push((lhs, false));
+                    continue;
+                }
+                Gate::Not(operand) => self.not(memo[&operand]),
+                Gate::And(lhs, rhs) => self.and(memo[&lhs], memo[&rhs]),
+                Gate::Or(lhs, rhs) => self.or(memo[&lhs], memo[&rhs]),
+                Gate::Xor(lhs, rhs) => self.xor(memo[&lhs], memo[&rhs]),
+            };
+            memo.insert(top, res);
+        }
+        memo[&node]
+    }
+
+    /// Gets the ids of the variables that a function depends on syntactically, sorted.
+    pub fn support(&self, node: usize) -> Vec<u32> {
+        let mut seen = vec![false; self.gates.len()];
+        let mut stack = vec![node];
+        let mut support = Vec::new();
+        while let Some(node) = stack.pop() {
+            if seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            match self.gates[node] {
+                Gate::Variable(id) => support.push(id),
+                Gate::Constant(_) => {}
+                Gate::Not(operand) => stack.push(operand),
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    stack.push(lhs);
+                    stack.push(rhs);
+                }
+            }
+        }
+        support.sort_unstable();
+        support
+    }
+
+    /// Gets the smallest and the largest variable that a function depends on syntactically, or None for
+    /// constants. The ranges of the visited nodes are kept in the given map, so once the operands of a gate
+    /// have ranges its own range takes constant time, unlike its support, which makes long chains of gates
+    /// cheap. The nodes are visited with an explicit stack, since the graph can be very deep.
+    pub fn range(
+        &self,
+        node: usize,
+        ranges: &mut HashMap<usize, Option<(u32, u32)>>,
+    ) -> Option<(u32, u32)> {
+        let mut stack = vec![node];
+        while let Some(&top) = stack.last() {
+            if ranges.contains_key(&top) {
+                stack.pop();
+                continue;
+            }
+            let operands = match self.gates[top] {
+                Gate::Variable(_) | Gate::Constant(_) => vec![],
+                Gate::Not(operand) => vec![operand],
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => vec![lhs, rhs],
+            };
+            let missing: Vec<usize> = operands
+                .iter()
+                .copied()
+                .filter(|operand| !ranges.contains_key(operand))
+                .collect();
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+            let range = match self.gates[top] {
+                Gate::Variable(id) => Some((id, id)),
+                _ => operands
+                    .iter()
+                    .filter_map(|operand| ranges[operand])
+                    .reduce(|(min, max), (other_min, other_max)| {
+                        (min.min(other_min), max.max(other_max))
+                    }),
+            };
+            ranges.insert(top, range);
+            stack.pop();
+        }
+        ranges[&node]
+    }
+
+    /// Converts a function of the graph back to a boolean expression.
+    /// `variable_names` are the names of the graph's variables by id. Only the variables that the function
+    /// depends on are kept in the expression and they are renumbered in the order of their first appearance,
+    /// the same way the parser does it.
+    /// A boolean expression is a tree, so the gates that are shared in the graph are written out every time
+    /// they are used, which can take exponentially many tokens. An error is returned instead of writing an
+    /// expression with more than MAX_EXPRESSION_TOKENS tokens.
+    pub fn to_expression<'source>(
+        &self,
+        node: usize,
+        variable_names: &[&'source str],
+    ) -> Result<BooleanExpression<'source>, EvaluationError> {
+        // The operands of a gate always come before it, so the sizes can be computed in index order
+        let mut sizes: Vec<usize> = Vec::with_capacity(node + 1);
+        for gate in &self.gates[..=node] {
+            let size = match *gate {
+                Gate::Variable(_) | Gate::Constant(_) => 1,
+                Gate::Not(operand) => sizes[operand].saturating_add(1),
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    sizes[lhs].saturating_add(sizes[rhs]).saturating_add(1)
+                }
+            };
+            sizes.push(size.min(MAX_EXPRESSION_TOKENS + 1));
+        }
+        if sizes[node] > MAX_EXPRESSION_TOKENS {
+            return Err(EvaluationError::TooLarge(MAX_EXPRESSION_TOKENS));
+        }
+
+        let mut ids = HashMap::new();
+        let mut variables = Vec::new();
+        let mut exp = Vec::with_capacity(sizes[node]);
+        // The gates are written in postfix order, each one after its operands
+        let mut stack = vec![(node, false)];
+        while let Some((node, operands_written)) = stack.pop() {
+            let op = match self.gates[node] {
+                Gate::Variable(id) => {
+                    let new_id = *ids.entry(id).or_insert_with(|| {
+                        variables.push(variable_names[id as usize]);
+                        variables.len() as u32 - 1
+                    });
+                    exp.push(BooleanExpressionToken::IDENT(new_id));
+                    continue;
+                }
+                Gate::Constant(value) => {
+                    exp.push(BooleanExpressionToken::RESULT(value));
+                    continue;
+                }
+                Gate::Not(operand) => {
+                    if !operands_written {
+                        stack.push((node, true));
+                        stack.push((operand, false));
+                        continue;
+                    }
+                    Token::NOT
+                }
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    if !operands_written {
+                        stack.push((node, true));
+                        stack.push((rhs, false));
+                        stack.push((lhs, false));
+                        continue;
+                    }
+                    match self.gates[node] {
+                        Gate::And(_, _) => Token::AND,
+                        Gate::Or(_, _) => Token::OR,
+                        _ => Token::XOR,
+                    }
+                }
+            };
+            exp.push(BooleanExpressionToken::OPERATOR(op));
+        }
+        Ok(BooleanExpression::new(exp, variables))
+    }
+
+    fn are_complements(&self, lhs: usize, rhs: usize) -> bool {
+        self.gates[lhs] == Gate::Not(rhs) || self.gates[rhs] == Gate::Not(lhs)
+    }
+
+    fn add(&mut self, gate: Gate) -> usize {
+        let gates = &mut self.gates;
+        *self.index.entry(gate).or_insert_with(|| {
+            gates.push(gate);
+            gates.len() - 1
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_expression_size_limit() {
+        let mut graph = ExpressionGraph::new();
+        let a = graph.variable(0);
+        let b = graph.variable(1);
+        let mut node = graph.and(a, b);
+        assert_eq!(
+            graph.to_expression(node, &["A", "B"]).unwrap().to_string(),
+            "A && B"
+        );
+        // Every level uses the previous one twice, so the tree doubles in size
+        for _ in 0..30 {
+            let lhs = graph.and(node, a);
+            let rhs = graph.and(node, b);
+            node = graph.or(lhs, rhs);
+        }
+        match graph.to_expression(node, &["A", "B"]) {
+            Err(EvaluationError::TooLarge(limit)) => assert_eq!(limit, MAX_EXPRESSION_TOKENS),
+            res => panic!("Unexpected result {:?}", res.map(|exp| exp.to_string())),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate lazy_static;
+
+pub mod aig;
+pub mod allsat;
+pub mod bdd;
+pub mod big_uint;
+pub mod bit_vector;
+pub mod bitstring_trait;
+pub mod boolean_expression;
+pub mod circuit;
+pub mod cnf;
+pub mod counting;
+pub mod csv;
+pub mod decision;
+pub mod expression_graph;
+pub mod logic;
+pub mod parser;
+pub mod probability;
+pub mod random;
+pub mod rows;
+pub mod sat;
+pub mod sensitivity;
+pub mod table_format;
+#[cfg(test)]
+mod test_util;
+pub mod token;
+pub mod zdd;
@@ -0,0 +1,254 @@
+use std::fmt;
+
+/// The operations of a logic whose values are not necessarily 0 and 1.
+/// Expressions can be evaluated in any such logic with BooleanExpression::evaluate_in.
+pub trait Logic {
+    type Value: Copy;
+
+    /// Converts a boolean constant (0 or 1) of an expression to a value of the logic.
+    fn constant(&self, value: u8) -> Self::Value;
+
+    fn not(&self, value: Self::Value) -> Self::Value;
+
+    fn and(&self, lhs: Self::Value, rhs: Self::Value) -> Self::Value;
+
+    fn or(&self, lhs: Self::Value, rhs: Self::Value) -> Self::Value;
+
+    fn xor(&self, lhs: Self::Value, rhs: Self::Value) -> Self::Value;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A value of a three-valued logic. Unknown stands for a value that can be either 0 or 1
+/// (X in hardware simulation) and is ordered between False and True.
+pub enum Ternary {
+    False,
+    Unknown,
+    True,
+}
+
+impl Ternary {
+    /// All the values in the order that they are enumerated in a truth table.
+    pub const VALUES: [Ternary; 3] = [Ternary::False, Ternary::Unknown, Ternary::True];
+
+    /// Parses a value written as 0, 1 or X (also false, true and unknown).
+    pub fn parse(value: &str) -> Option<Ternary> {
+        match value {
+            "0" | "false" => Some(Ternary::False),
+            "1" | "true" => Some(Ternary::True),
+            "X" | "x" | "unknown" => Some(Ternary::Unknown),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    // The value counted in halves, i.e 0, 1/2 and 1 become 0, 1 and 2
+    fn halves(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    fn from_halves(halves: u8) -> Ternary {
+        Ternary::VALUES[halves as usize]
+    }
+}
+
+impl fmt::Display for Ternary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            Ternary::False => "0",
+            Ternary::Unknown => "X",
+            Ternary::True => "1",
+        };
+        // pad makes the value respect the width of the format string
+        f.pad(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The three-valued logics that batt can evaluate expressions in.
+/// Kleene is the strong Kleene logic: the result is unknown unless the known operands decide it,
+/// i.e "0 && X" is 0 but "1 && X" and "X ^ X" are X.
+/// Lukasiewicz is the logic Ł3, where X is the truth degree 1/2. It only differs from Kleene logic in xor,
+/// which is |a - b|, so "X ^ X" is 0 instead of X. "&&", "||" and "!" are min, max and 1 - a in both logics.
+pub enum ThreeValuedLogic {
+    Kleene,
+    Lukasiewicz,
+}
+
+impl Logic for ThreeValuedLogic {
+    type Value = Ternary;
+
+    fn constant(&self, value: u8) -> Ternary {
+        if value == 0 {
+            Ternary::False
+        } else {
+            Ternary::True
+        }
+    }
+
+    fn not(&self, value: Ternary) -> Ternary {
+        Ternary::from_halves(2 - value.halves())
+    }
+
+    fn and(&self, lhs: Ternary, rhs: Ternary) -> Ternary {
+        match self {
+            ThreeValuedLogic::Kleene | ThreeValuedLogic::Lukasiewicz => lhs.min(rhs),
+        }
+    }
+
+    fn or(&self, lhs: Ternary, rhs: Ternary) -> Ternary {
+        match self {
+            ThreeValuedLogic::Kleene | ThreeValuedLogic::Lukasiewicz => lhs.max(rhs),
+        }
+    }
+
+    fn xor(&self, lhs: Ternary, rhs: Ternary) -> Ternary {
+        match self {
+            ThreeValuedLogic::Kleene => {
+                if lhs == Ternary::Unknown || rhs == Ternary::Unknown {
+                    Ternary::Unknown
+                } else {
+                    self.constant((lhs != rhs) as u8)
+                }
+            }
+            ThreeValuedLogic::Lukasiewicz => {
+                Ternary::from_halves((lhs.halves() as i8 - rhs.halves() as i8).unsigned_abs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Fuzzy logics, where values are degrees of truth in [0, 1], named after their t-norm (the
+/// operation used for AND). OR is the dual t-conorm, NOT is 1 - a and XOR is defined as
+/// (a AND NOT b) OR (NOT a AND b) with those operations.
+/// Min is Zadeh's (Gödel) logic: min(a, b) and max(a, b).
+/// Product is the product t-norm a * b with the probabilistic sum a + b - a * b.
+/// Lukasiewicz is max(0, a + b - 1) with the bounded sum min(1, a + b).
+/// All of them agree with boolean logic when the values are 0 or 1.
+pub enum FuzzyLogic {
+    Min,
+    Product,
+    Lukasiewicz,
+}
+
+impl Logic for FuzzyLogic {
+    type Value = f64;
+
+    fn constant(&self, value: u8) -> f64 {
+        value as f64
+    }
+
+    fn not(&self, value: f64) -> f64 {
+        1.0 - value
+    }
+
+    fn and(&self, lhs: f64, rhs: f64) -> f64 {
+        match self {
+            FuzzyLogic::Min => lhs.min(rhs),
+            FuzzyLogic::Product => lhs * rhs,
+            FuzzyLogic::Lukasiewicz => (lhs + rhs - 1.0).max(0.0),
+        }
+    }
+
+    fn or(&self, lhs: f64, rhs: f64) -> f64 {
+        match self {
+            FuzzyLogic::Min => lhs.max(rhs),
+            FuzzyLogic::Product => lhs + rhs - lhs * rhs,
+            FuzzyLogic::Lukasiewicz => (lhs + rhs).min(1.0),
+        }
+    }
+
+    fn xor(&self, lhs: f64, rhs: f64) -> f64 {
+        let only_lhs = self.and(lhs, self.not(rhs));
+        let only_rhs = self.and(self.not(lhs), rhs);
+        self.or(only_lhs, only_rhs)
+    }
+}
+
+/// Moves a three-valued assignment to the next row of a three-valued truth table, where the last
+/// value changes the fastest, and returns false when it wraps around to the first row.
+pub fn next_ternary_assignment(values: &mut [Ternary]) -> bool {
+    for value in values.iter_mut().rev() {
+        if *value == Ternary::True {
+            *value = Ternary::False;
+        } else {
+            *value = Ternary::from_halves(value.halves() + 1);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_kleene() {
+        let exp = Parser::new(Token::lexer("A && B || !A")).parse().unwrap();
+        let values = [Ternary::Unknown, Ternary::True];
+        let logic = ThreeValuedLogic::Kleene;
+        assert_eq!(
+            exp.evaluate_in(&logic, |id| values[id as usize]),
+            Ternary::Unknown
+        );
+        let values = [Ternary::False, Ternary::Unknown];
+        assert_eq!(
+            exp.evaluate_in(&logic, |id| values[id as usize]),
+            Ternary::True
+        );
+    }
+
+    #[test]
+    fn test_lukasiewicz() {
+        let logic = ThreeValuedLogic::Lukasiewicz;
+        let x = Ternary::Unknown;
+        assert_eq!(logic.and(x, x), x);
+        assert_eq!(logic.or(x, x), x);
+        assert_eq!(logic.xor(x, x), Ternary::False);
+        assert_eq!(logic.xor(x, Ternary::True), x);
+        assert_eq!(logic.or(x, Ternary::True), Ternary::True);
+        assert_eq!(logic.and(x, Ternary::True), x);
+        assert_eq!(logic.not(x), x);
+    }
+
+    #[test]
+    fn test_xor_of_unknowns() {
+        let exp = Parser::new(Token::lexer("A ^ A")).parse().unwrap();
+        let eval = |logic: ThreeValuedLogic| exp.evaluate_in(&logic, |_| Ternary::Unknown);
+        assert_eq!(eval(ThreeValuedLogic::Lukasiewicz), Ternary::False);
+        assert_eq!(eval(ThreeValuedLogic::Kleene), Ternary::Unknown);
+    }
+
+    #[test]
+    fn test_fuzzy_logics() {
+        let exp = Parser::new(Token::lexer("A && B || !C")).parse().unwrap();
+        let values = [0.5, 0.8, 0.9];
+        let eval = |logic: FuzzyLogic| exp.evaluate_in(&logic, |id| values[id as usize]);
+        assert!((eval(FuzzyLogic::Min) - 0.5).abs() < 1e-12);
+        assert!((eval(FuzzyLogic::Product) - (0.4 + 0.1 - 0.04)).abs() < 1e-12);
+        assert!((eval(FuzzyLogic::Lukasiewicz) - 0.4).abs() < 1e-12);
+        for logic in &[
+            FuzzyLogic::Min,
+            FuzzyLogic::Product,
+            FuzzyLogic::Lukasiewicz,
+        ] {
+            assert_eq!(logic.xor(1.0, 0.0), 1.0);
+            assert_eq!(logic.xor(1.0, 1.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_ternary_rows() {
+        let mut values = [Ternary::False; 3];
+        let mut rows = 1;
+        while next_ternary_assignment(&mut values) {
+            rows += 1;
+        }
+        assert_eq!(rows, 27);
+    }
+}
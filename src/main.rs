@@ -1,35 +1,839 @@
-#[macro_use]
-extern crate lazy_static;
-
-mod bitstring_trait;
-mod boolean_expression;
-mod parser;
-mod table_format;
-mod token;
+mod cli;
 
+use batt::aig::Aig;
+use batt::allsat::Models;
+use batt::bdd::BddManager;
+use batt::bit_vector::BitVector;
+use batt::boolean_expression::{BooleanExpression, EvaluationError};
+use batt::counting;
+use batt::csv;
+use batt::decision::{self, Classification};
+use batt::logic::{next_ternary_assignment, FuzzyLogic, Ternary, ThreeValuedLogic};
+use batt::parser::Parser;
+use batt::probability;
+use batt::random::Random;
+use batt::rows::{self, number_of_rows, RowFilter, Rows};
+use batt::sat;
+use batt::sensitivity;
+use batt::table_format::TableFormat;
+use batt::token::*;
+use batt::zdd::ZddManager;
+use cli::{report_error, Command, Options, USAGE};
 use logos::Logos;
-use parser::Parser;
-use std::io;
-use table_format::TableFormat;
-use token::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, io, process, vec};
+
+/// The number of rows above which a warning is printed when there's no --limit option. It is also the limit
+/// of the computations that enumerate the truth table without printing it.
+const DEFAULT_ROW_LIMIT: u128 = 1 << 16;
+
+/// The exit codes of --classify. Tautologies exit with 0.
+const CONTRADICTION_EXIT_CODE: i32 = 3;
+const CONTINGENCY_EXIT_CODE: i32 = 4;
+
+/// The exit code of the commands that check a relation between two expressions when it doesn't hold.
+const RELATION_FAILS_EXIT_CODE: i32 = 3;
 
 fn main() {
-    let mut exp = String::new();
-    io::stdin()
-        .read_line(&mut exp)
-        .expect("Something went wrong when reading input from stdin");
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            report_error(&msg);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
+    match &options.command {
+        Some(command) if command.takes_two_expressions() => {
+            run_command(command);
+            return;
+        }
+        _ => {}
+    }
+
+    let exp = match &options.expression {
+        Some(exp) => exp.clone(),
+        None => {
+            let mut exp = String::new();
+            io::stdin()
+                .read_line(&mut exp)
+                .expect("Something went wrong when reading input from stdin");
+            exp
+        }
+    };
 
     let exp = exp.trim();
-    if let Some(bexp) = Parser::new(Token::lexer(&exp)).parse() {
-        let variables = bexp.variables();
-        let number_of_vars = variables.len();
-
-        let table_format = TableFormat::new(&exp, &bexp);
-        table_format.print_header();
-        for i in 0..(2 << (number_of_vars - 1)) as u128 {
-            let res = bexp.evaluate(i);
-            table_format.print_evaluation(&bexp, i, res);
+    let bexp = match Parser::new(Token::lexer(exp)).parse() {
+        Some(bexp) => bexp,
+        None => process::exit(1),
+    };
+
+    let mut replacements = Vec::with_capacity(options.substitutions.len());
+    for (name, replacement) in &options.substitutions {
+        match Parser::new(Token::lexer(replacement)).parse() {
+            Some(replacement) => replacements.push((name.as_str(), replacement)),
+            None => process::exit(1),
+        }
+    }
+    let composed_exp;
+    let (exp, bexp) = if replacements.is_empty() {
+        (exp, bexp)
+    } else {
+        let substitutions: Vec<(&str, &BooleanExpression)> = replacements
+            .iter()
+            .map(|(name, replacement)| (*name, replacement))
+            .collect();
+        match bexp.substitute(&substitutions) {
+            Ok(composed) => {
+                composed_exp = composed.to_string();
+                if options.eval.is_none() && options.csv.is_none() {
+                    println!("Composed expression: {}", composed_exp);
+                }
+                (composed_exp.as_str(), composed)
+            }
+            Err(err) => {
+                report_error(&err.to_string());
+                process::exit(1);
+            }
+        }
+    };
+
+    let reduced_exp;
+    let (exp, bexp) = match &options.fix {
+        Some(assignment) => {
+            let assignment: HashMap<&str, bool> = assignment
+                .iter()
+                .map(|(name, value)| (name.as_str(), *value))
+                .collect();
+            match bexp.restrict(&assignment) {
+                Ok(reduced) => {
+                    reduced_exp = reduced.to_string();
+                    if options.eval.is_none() && options.csv.is_none() {
+                        println!("Reduced expression: {}", reduced_exp);
+                    }
+                    (reduced_exp.as_str(), reduced)
+                }
+                Err(err) => {
+                    report_error(&err.to_string());
+                    process::exit(1);
+                }
+            }
+        }
+        None => (exp, bexp),
+    };
+
+    let derivative_exp;
+    let (exp, bexp) = match &options.derivative {
+        Some(name) => match bexp.variables().iter().position(|var| var == name) {
+            Some(id) => match sensitivity::derivative(&bexp, id as u32) {
+                Ok(derivative) => {
+                    derivative_exp = derivative.to_string();
+                    if options.eval.is_none() && options.csv.is_none() {
+                        println!("Derivative: {}", derivative_exp);
+                    }
+                    (derivative_exp.as_str(), derivative)
+                }
+                Err(err) => {
+                    report_error(&err.to_string());
+                    process::exit(1);
+                }
+            },
+            None => {
+                report_error(&EvaluationError::UnknownVariable(name.clone()).to_string());
+                process::exit(1);
+            }
+        },
+        None => (exp, bexp),
+    };
+
+    if options.command == Some(Command::Sat) {
+        solve(&bexp);
+    } else if options.command == Some(Command::Models) {
+        print_models(&bexp, &options);
+    } else if options.command == Some(Command::Count) {
+        let models = counting::count_models(&bexp);
+        let number_of_vars = bexp.variables().len();
+        println!("Models: {}", models);
+        println!(
+            "Fraction of the input space: {:.12} (of 2^{} rows)",
+            models.div_power_of_two(number_of_vars),
+            number_of_vars
+        );
+    } else if options.command == Some(Command::Bdd) {
+        print_bdd(&bexp, &options);
+    } else if options.command == Some(Command::Zdd) {
+        print_zdd(&bexp, &options);
+    } else if options.command == Some(Command::Aig) {
+        print_aig(&bexp, &options);
+    } else if let Some(assignment) = &options.eval {
+        evaluate_assignment(&bexp, assignment, &options);
+    } else if options.classify {
+        classify(&bexp);
+    } else if options.influence {
+        print_influence(&bexp, &options);
+    } else if options.probability {
+        let probabilities = variable_probabilities_or_exit(&bexp, &options);
+        println!(
+            "Probability: {:.12}",
+            probability::exact_probability(&bexp, &probabilities)
+        );
+    } else if let Some(samples) = options.estimate {
+        estimate_probability(&bexp, samples, &options);
+    } else if let Some(path) = &options.csv {
+        evaluate_csv_file(exp, &bexp, path);
+    } else if let Some(logic) = options.fuzzy {
+        print_fuzzy_truth_table(exp, &bexp, logic, &options);
+    } else if let Some(logic) = options.logic {
+        print_ternary_truth_table(exp, &bexp, logic, &options);
+    } else {
+        print_truth_table(exp, &bexp, &options);
+    }
+}
+
+fn evaluate_assignment(
+    bexp: &BooleanExpression,
+    assignment: &[(String, String)],
+    options: &Options,
+) {
+    let res = match (options.logic, options.fuzzy) {
+        (_, Some(logic)) => parse_values(assignment, cli::parse_probability)
+            .and_then(|assignment| {
+                bexp.values_by_id(&assignment)
+                    .map_err(|err| err.to_string())
+            })
+            .map(|values| {
+                let res = bexp.evaluate_in(&logic, |id| values[id as usize]);
+                format!("{:.6}", res)
+            }),
+        (Some(logic), _) => parse_values(assignment, |value| {
+            Ternary::parse(value).ok_or_else(|| format!("Expected 0, 1 or X but got '{}'", value))
+        })
+        .and_then(|assignment| {
+            bexp.values_by_id(&assignment)
+                .map_err(|err| err.to_string())
+        })
+        .map(|values| {
+            bexp.evaluate_in(&logic, |id| values[id as usize])
+                .to_string()
+        }),
+        (None, None) => parse_values(assignment, cli::parse_bool).and_then(|assignment| {
+            bexp.evaluate_named(&assignment)
+                .map(|res| (res as u8).to_string())
+                .map_err(|err| err.to_string())
+        }),
+    };
+    match res {
+        Ok(res) => println!("{}", res),
+        Err(msg) => {
+            report_error(&msg);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_values<T, F>(
+    assignment: &[(String, String)],
+    parse_value: F,
+) -> Result<HashMap<&str, T>, String>
+where
+    F: Fn(&str) -> Result<T, String>,
+{
+    assignment
+        .iter()
+        .map(|(name, value)| Ok((name.as_str(), parse_value(value)?)))
+        .collect()
+}
+
+/// Prints the truth table of the expression in a three-valued logic, which has 3^N rows.
+fn print_ternary_truth_table(
+    exp: &str,
+    bexp: &BooleanExpression,
+    logic: ThreeValuedLogic,
+    options: &Options,
+) {
+    let number_of_vars = bexp.variables().len();
+    let rows = 3u128.checked_pow(number_of_vars as u32);
+    let description = format!("3^{}", number_of_vars);
+    if let Err(err) = check_row_count(rows, &description, "Use --limit to raise it", options) {
+        report_error(&err);
+        process::exit(1);
+    }
+
+    let table_format = TableFormat::new(exp, bexp);
+    table_format.print_header();
+    let mut values = vec![Ternary::False; number_of_vars];
+    let mut shown = 0u128;
+    let mut hidden = 0u128;
+    loop {
+        let res = bexp.evaluate_in(&logic, |id| values[id as usize]);
+        let is_shown = match options.only {
+            Some(1) => res == Ternary::True,
+            Some(_) => res == Ternary::False,
+            None => true,
+        };
+        if is_shown {
+            table_format.print_ternary_evaluation(&values, res);
+            table_format.print_row_separator();
+            shown += 1;
+        } else {
+            hidden += 1;
+        }
+        if !next_ternary_assignment(&mut values) {
+            break;
+        }
+    }
+    if options.only.is_some() {
+        println!("Shown {} rows, hidden {} rows", shown, hidden);
+    }
+}
+
+/// Prints the truth table of the expression in a fuzzy logic, where every variable takes
+/// grid points evenly spaced values from 0 to 1.
+fn print_fuzzy_truth_table(
+    exp: &str,
+    bexp: &BooleanExpression,
+    logic: FuzzyLogic,
+    options: &Options,
+) {
+    let number_of_vars = bexp.variables().len();
+    let points = options.grid.unwrap_or(3);
+    let rows = (points as u128).checked_pow(number_of_vars as u32);
+    let description = format!("{}^{}", points, number_of_vars);
+    if let Err(err) = check_row_count(rows, &description, "Use --limit to raise it", options) {
+        report_error(&err);
+        process::exit(1);
+    }
+
+    let precision = 3;
+    let table_format = TableFormat::with_column_width(exp, bexp, precision + 2);
+    table_format.print_header();
+    let mut indices = vec![0; number_of_vars];
+    let mut values = vec![0.0; number_of_vars];
+    let mut shown = 0u128;
+    let mut hidden = 0u128;
+    loop {
+        for (value, index) in values.iter_mut().zip(&indices) {
+            *value = *index as f64 / (points - 1) as f64;
+        }
+        let res = bexp.evaluate_in(&logic, |id| values[id as usize]);
+        let is_shown = match options.only {
+            Some(1) => res == 1.0,
+            Some(_) => res == 0.0,
+            None => true,
+        };
+        if is_shown {
+            table_format.print_fuzzy_evaluation(&values, res, precision);
             table_format.print_row_separator();
+            shown += 1;
+        } else {
+            hidden += 1;
+        }
+        // Move to the next grid point, the last variable changes the fastest
+        match indices.iter().rposition(|index| *index + 1 < points) {
+            Some(i) => {
+                indices[i] += 1;
+                for index in &mut indices[i + 1..] {
+                    *index = 0;
+                }
+            }
+            None => break,
+        }
+    }
+    if options.only.is_some() {
+        println!("Shown {} rows, hidden {} rows", shown, hidden);
+    }
+}
+
+fn evaluate_csv_file(exp: &str, bexp: &BooleanExpression, path: &str) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            report_error(&format!("Could not open '{}': {}", path, err));
+            process::exit(1);
+        }
+    };
+    let stdout = io::stdout();
+    match csv::evaluate_csv(bexp, exp, BufReader::new(file), stdout.lock()) {
+        Ok(summary) => eprintln!("{} of {} rows are true", summary.true_rows, summary.rows),
+        Err(err) => {
+            report_error(&format!("{}: {}", path, err));
+            process::exit(1);
+        }
+    }
+}
+
+fn estimate_probability(bexp: &BooleanExpression, samples: u64, options: &Options) {
+    let probabilities = variable_probabilities_or_exit(bexp, options);
+    let confidence = options.confidence.unwrap_or(0.95);
+    let estimate = probability::estimate(
+        bexp,
+        &probabilities,
+        samples,
+        confidence,
+        &mut random(options),
+    );
+    println!("Estimated probability: {:.6}", estimate.probability);
+    println!(
+        "Samples: {} ({} satisfying)",
+        estimate.samples, estimate.satisfying
+    );
+    println!(
+        "{}% confidence interval: [{:.6}, {:.6}] (error bound {:.6})",
+        estimate.confidence * 100.0,
+        estimate.lower,
+        estimate.upper,
+        estimate.error_bound()
+    );
+}
+
+/// Prints the classification of the expression with its witnesses and exits with the matching code.
+fn classify(bexp: &BooleanExpression) {
+    let classification = decision::classify(bexp);
+    let code = match classification {
+        Classification::Tautology { .. } => {
+            println!("Tautology");
+            0
+        }
+        Classification::Contradiction { .. } => {
+            println!("Contradiction");
+            CONTRADICTION_EXIT_CODE
+        }
+        Classification::Contingency { .. } => {
+            println!("Contingency");
+            CONTINGENCY_EXIT_CODE
+        }
+    };
+    if let Some(satisfying) = classification.satisfying() {
+        println!(
+            "Satisfying assignment: {}",
+            format_assignment(bexp.variables(), satisfying)
+        );
+    }
+    if let Some(falsifying) = classification.falsifying() {
+        println!(
+            "Falsifying assignment: {}",
+            format_assignment(bexp.variables(), falsifying)
+        );
+    }
+    process::exit(code);
+}
+
+/// Runs a command on two expressions and exits with RELATION_FAILS_EXIT_CODE if the relation doesn't hold.
+fn run_command(command: &Command) {
+    let (lhs_exp, rhs_exp) = match command {
+        Command::Equiv(lhs, rhs) | Command::Implies(lhs, rhs) | Command::Disjoint(lhs, rhs) => {
+            (lhs.trim(), rhs.trim())
+        }
+        _ => unreachable!("The command works on a single expression"),
+    };
+    let lhs = parse_or_exit(lhs_exp);
+    let rhs = parse_or_exit(rhs_exp);
+    let (relation, holds, fails, counterexample_name) = match command {
+        Command::Equiv(_, _) => (
+            decision::equivalence(&lhs, &rhs),
+            "Equivalent",
+            "Not equivalent",
+            "Distinguishing assignment",
+        ),
+        Command::Implies(_, _) => (
+            decision::entailment(&lhs, &rhs),
+            "The first expression implies the second",
+            "The first expression doesn't imply the second",
+            "Counterexample",
+        ),
+        Command::Disjoint(_, _) => (
+            decision::mutual_exclusion(&lhs, &rhs),
+            "Mutually exclusive",
+            "Not mutually exclusive",
+            "Common satisfying assignment",
+        ),
+        _ => unreachable!("The command works on a single expression"),
+    };
+    match &relation.counterexample {
+        None => println!("{}", holds),
+        Some(counterexample) => {
+            println!("{}", fails);
+            println!(
+                "{}: {}",
+                counterexample_name,
+                format_assignment(&relation.variables, &counterexample.assignment)
+            );
+            println!("{} = {}", lhs_exp, counterexample.lhs);
+            println!("{} = {}", rhs_exp, counterexample.rhs);
+            process::exit(RELATION_FAILS_EXIT_CODE);
+        }
+    }
+}
+
+/// Searches for a satisfying assignment with the SAT solver and exits with CONTRADICTION_EXIT_CODE if there is none.
+/// The size of the CNF and the number of conflicts are printed to stderr.
+fn solve(bexp: &BooleanExpression) {
+    let solution = sat::solve_with_statistics(bexp);
+    eprintln!(
+        "CNF with {} variables and {} clauses, solved after {} conflicts",
+        solution.cnf_vars, solution.cnf_clauses, solution.conflicts
+    );
+    match solution.model {
+        Some(values) => {
+            println!("Satisfiable");
+            println!(
+                "Satisfying assignment: {}",
+                format_assignment(bexp.variables(), &values)
+            );
         }
+        None => {
+            println!("Unsatisfiable");
+            process::exit(CONTRADICTION_EXIT_CODE);
+        }
+    }
+}
+
+/// Prints the models of the expression one per line as soon as they are found, up to --max of them.
+/// Don't cares of cubes are printed as -.
+fn print_models(bexp: &BooleanExpression, options: &Options) {
+    let mut models = Models::new(bexp);
+    if options.cubes {
+        models = models.with_cubes();
+    }
+    let max_models = options.max_models.unwrap_or(usize::MAX);
+    let mut found = 0;
+    let variable_names = bexp.variables();
+    for model in models.take(max_models) {
+        println!("{}", format_cube(variable_names, &model));
+        found += 1;
+    }
+    let kind = if options.cubes { "cubes" } else { "models" };
+    if found == max_models {
+        eprintln!("Stopped after {} {}", found, kind);
+    } else {
+        eprintln!("Found {} {}", found, kind);
+    }
+}
+
+fn print_bdd(bexp: &BooleanExpression, options: &Options) {
+    let (mut manager, root) = BddManager::from_expression_with_order(bexp, options.var_order);
+    let variable_names = bexp.variables();
+    let format_order = |manager: &BddManager| {
+        let names: Vec<&str> = manager
+            .order()
+            .iter()
+            .map(|var| variable_names[*var as usize])
+            .collect();
+        names.join(", ")
+    };
+    println!("Variable order: {}", format_order(&manager));
+    println!("BDD nodes: {}", manager.node_count(root));
+    if options.sift {
+        manager.sift(&[root]);
+        println!("Variable order after sifting: {}", format_order(&manager));
+        println!("BDD nodes after sifting: {}", manager.node_count(root));
+    }
+    println!("Models: {}", manager.sat_count(root));
+    match manager.any_sat(root) {
+        Some(cube) => println!("Satisfying cube: {}", format_cube(bexp.variables(), &cube)),
+        None => println!("Unsatisfiable"),
+    }
+}
+
+fn print_zdd(bexp: &BooleanExpression, options: &Options) {
+    let (manager, family) = ZddManager::from_expression(bexp);
+    let (bdd_manager, root) = BddManager::from_expression(bexp);
+    println!("ZDD nodes: {}", manager.node_count(family));
+    println!("BDD nodes: {}", bdd_manager.node_count(root));
+    println!("Sets: {}", manager.count(family));
+    let variable_names = bexp.variables();
+    let max_sets = options.max_models.unwrap_or(usize::MAX);
+    let mut listed = 0;
+    for set in manager.sets(family).take(max_sets) {
+        let names: Vec<&str> = set
+            .iter()
+            .map(|var| variable_names[*var as usize])
+            .collect();
+        println!("{{{}}}", names.join(", "));
+        listed += 1;
+    }
+    if listed == max_sets {
+        eprintln!("Stopped after {} sets", listed);
+    }
+}
+
+fn print_aig(bexp: &BooleanExpression, options: &Options) {
+    let (mut aig, mut root) = Aig::from_expression(bexp);
+    if !options.aiger {
+        println!("AND nodes: {}", aig.and_count(root));
+        println!("Depth: {}", aig.depth(root));
+    }
+    if options.balance {
+        root = aig.balance(root);
+        if !options.aiger {
+            println!("AND nodes after balancing: {}", aig.and_count(root));
+            println!("Depth after balancing: {}", aig.depth(root));
+        }
+    }
+    if options.aiger {
+        print!("{}", aig.to_aiger(root, bexp.variables()));
+    }
+}
+
+fn parse_or_exit(exp: &str) -> BooleanExpression<'_> {
+    match Parser::new(Token::lexer(exp)).parse() {
+        Some(bexp) => bexp,
+        None => process::exit(1),
+    }
+}
+
+/// Formats a partial assignment indexed by variable id, with - for the unassigned variables.
+fn format_cube(variable_names: &[&str], values: &[Option<u8>]) -> String {
+    variable_names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| match value {
+            Some(value) => format!("{}={}", name, value),
+            None => format!("{}=-", name),
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Formats an assignment indexed by variable id the same way as the --eval option takes it.
+fn format_assignment(variable_names: &[&str], values: &[u8]) -> String {
+    if variable_names.is_empty() {
+        return "(no variables)".to_owned();
+    }
+    variable_names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Prints the influence, the normalized Banzhaf index and the Shapley value of every variable.
+/// The Shapley values and the maximum sensitivity need the whole truth table, so they are left out
+/// when it has more rows than the row limit.
+fn print_influence(bexp: &BooleanExpression, options: &Options) {
+    let influences = sensitivity::influences(bexp);
+    let total: f64 = influences.iter().sum();
+    let limit = options.limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    let sensitivity = number_of_rows(bexp.variables().len())
+        .filter(|rows| *rows <= limit)
+        .map(|_| sensitivity::enumerate_sensitivity(bexp));
+
+    let width = bexp
+        .variables()
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Variable".len());
+    println!(
+        "{:<width$}  {:>9}  {:>9}  {:>9}",
+        "Variable",
+        "Influence",
+        "Banzhaf",
+        "Shapley",
+        width = width
+    );
+    for (id, name) in bexp.variables().iter().enumerate() {
+        let banzhaf = if total > 0.0 {
+            influences[id] / total
+        } else {
+            0.0
+        };
+        let shapley = match &sensitivity {
+            Some(sensitivity) => format!("{:.6}", sensitivity.shapley[id]),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{:<width$}  {:>9.6}  {:>9.6}  {:>9}",
+            name,
+            influences[id],
+            banzhaf,
+            shapley,
+            width = width
+        );
+    }
+    println!("Total influence (average sensitivity): {:.6}", total);
+    match &sensitivity {
+        Some(sensitivity) => println!("Maximum sensitivity: {}", sensitivity.max_sensitivity),
+        None => eprintln!(
+            "The truth table has more than {} rows, so Shapley values are not computed",
+            limit
+        ),
+    }
+}
+
+fn variable_probabilities_or_exit(bexp: &BooleanExpression, options: &Options) -> Vec<f64> {
+    let probabilities: HashMap<&str, f64> = options
+        .probabilities
+        .iter()
+        .map(|(name, probability)| (name.as_str(), *probability))
+        .collect();
+    match probability::variable_probabilities(bexp, &probabilities) {
+        Ok(probabilities) => probabilities,
+        Err(err) => {
+            report_error(&err.to_string());
+            process::exit(1);
+        }
+    }
+}
+
+/// Creates the random number generator from the --seed option or from the current time,
+/// in which case the seed is printed so that the results can be reproduced.
+fn random(options: &Options) -> Random {
+    let seed = options.seed.unwrap_or_else(|| {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        eprintln!("Using random seed {}", seed);
+        seed
+    });
+    Random::new(seed)
+}
+
+fn print_truth_table(exp: &str, bexp: &BooleanExpression, options: &Options) {
+    let condition = match &options.condition {
+        Some(condition) => match Parser::new(Token::lexer(condition)).parse() {
+            Some(condition) => Some(condition),
+            None => process::exit(1),
+        },
+        None => None,
+    };
+
+    let mut filter = RowFilter::new();
+    if let Some(result) = options.only {
+        filter = filter.with_result(result);
+    }
+    if let Some(condition) = &condition {
+        filter = match filter.with_condition(bexp, condition) {
+            Ok(filter) => filter,
+            Err(err) => {
+                report_error(&format!("In condition: {}", err));
+                process::exit(1);
+            }
+        };
+    }
+
+    let table_format = if options.subexpressions {
+        TableFormat::with_subexpressions(exp, bexp)
+    } else {
+        TableFormat::new(exp, bexp)
+    };
+    let (mut rows, mut sample) = match select_rows(bexp, options) {
+        Ok(rows) => rows,
+        Err(msg) => {
+            report_error(&msg);
+            process::exit(1);
+        }
+    };
+    table_format.print_header();
+    let mut shown = 0u128;
+    let mut hidden = 0u128;
+    loop {
+        let next = match &mut sample {
+            Some(sample) => sample.next().map(|row| rows.get(row)),
+            None => rows.next(),
+        };
+        let (input, res) = match next {
+            Some(row) => row,
+            None => break,
+        };
+        if filter.matches(&input, res) {
+            table_format.print_evaluation(bexp, &input, rows.gate_values(), res);
+            table_format.print_row_separator();
+            shown += 1;
+        } else {
+            hidden += 1;
+        }
+    }
+    if options.only.is_some() || condition.is_some() {
+        println!("Shown {} rows, hidden {} rows", shown, hidden);
+    }
+}
+
+/// Picks the rows of the truth table that were asked for by the --rows and --sample options
+/// and makes sure that they can be printed (see check_row_count).
+/// Either all the rows of the returned iterator are printed, or only the given row numbers, which are
+/// evaluated with Rows::get. The subexpression columns read the gate values of the same Rows.
+fn select_rows(
+    bexp: &BooleanExpression,
+    options: &Options,
+) -> Result<(Rows, Option<vec::IntoIter<BitVector>>), String> {
+    let number_of_vars = bexp.variables().len();
+    let total = number_of_rows(number_of_vars);
+    let mut rows = Rows::new(bexp, options.order);
+
+    if let Some(count) = options.sample {
+        check_row_count(
+            Some(count as u128),
+            &count.to_string(),
+            "Use --limit to raise it",
+            options,
+        )?;
+        let sample = rows::sample(number_of_vars, count, &mut random(options));
+        return Ok((rows, Some(sample.into_iter())));
+    }
+
+    let (start, end) = options.rows.unwrap_or((None, None));
+    let start = start.unwrap_or(0);
+    let end = match (end, total) {
+        (Some(end), Some(total)) if end > total => {
+            return Err(format!("The truth table has only {} rows", total))
+        }
+        (Some(end), _) => Some(end),
+        (None, total) => total,
+    };
+    let count = end.map(|end| end.saturating_sub(start));
+    check_row_count(
+        count,
+        &count.map_or_else(
+            || format!("2^{}", number_of_vars),
+            |count| count.to_string(),
+        ),
+        "Use --rows or --sample to print a part of the table or --limit to raise it",
+        options,
+    )?;
+    if start > 0 {
+        rows = rows.starting_at(BitVector::from_u128(number_of_vars, start));
+    }
+    if let Some(end) = end {
+        if start >= end {
+            return Ok((rows, Some(Vec::new().into_iter())));
+        }
+        rows = rows.ending_at(BitVector::from_u128(number_of_vars, end - 1));
+    }
+    Ok((rows, None))
+}
+
+/// Makes sure that a table with the given number of rows, or more than u128::MAX if it is None,
+/// can be printed. The tables with more rows than --limit are refused, with the given advice.
+/// Without --limit every table is printed, with a warning when it has more than DEFAULT_ROW_LIMIT rows.
+fn check_row_count(
+    rows: Option<u128>,
+    description: &str,
+    advice: &str,
+    options: &Options,
+) -> Result<(), String> {
+    let limit = options.limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    if matches!(rows, Some(rows) if rows <= limit) {
+        return Ok(());
+    }
+    if options.limit.is_some() {
+        return Err(format!(
+            "Refusing to print {} rows, the limit is {} rows. {}",
+            description, limit, advice
+        ));
     }
+    eprintln!(
+        "Printing {} rows. Use --limit N to refuse to print more than N rows",
+        description
+    );
+    Ok(())
 }
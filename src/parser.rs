@@ -180,8 +180,9 @@ impl<'source> Parser<'source> {
             &self.source[token_span.end..]
         );
         eprintln!(
-            "{}{}",
-            format!("{: <1$}", "", token_span.start + ERROR_TAG.len()),
+            "{: <1$}{2}",
+            "",
+            token_span.start + ERROR_TAG.len(),
             "^".yellow()
         );
         eprintln!(
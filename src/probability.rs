@@ -0,0 +1,409 @@
+use crate::bitstring_trait::*;
+use crate::boolean_expression::*;
+use crate::circuit::Gate;
+use crate::expression_graph::ExpressionGraph;
+use crate::random::Random;
+use crate::rows::{RowOrder, Rows};
+use std::collections::{HashMap, HashSet};
+
+/// Maps probabilities given by variable name to a vector indexed by variable id.
+/// Variables without a probability get 0.5, which makes every row equally likely.
+/// Returns an error for names that are not variables of the expression.
+pub fn variable_probabilities(
+    bexp: &BooleanExpression,
+    probabilities: &HashMap<&str, f64>,
+) -> Result<Vec<f64>, EvaluationError> {
+    if let Some(name) = probabilities
+        .keys()
+        .find(|name| !bexp.variables().contains(name))
+    {
+        return Err(EvaluationError::UnknownVariable((*name).to_owned()));
+    }
+    Ok(bexp
+        .variables()
+        .iter()
+        .map(|name| *probabilities.get(name).unwrap_or(&0.5))
+        .collect())
+}
+
+/// The largest number of variables for which exact_probability sums over the truth table
+/// instead of using Shannon expansion.
+pub const ENUMERATION_LIMIT: usize = 12;
+
+/// Computes the exact probability that the expression is true when every variable is independently
+/// true with the probability at its id.
+/// Small expressions are handled by summing the probabilities of the satisfying rows of the truth table.
+/// Larger ones are handled by Shannon expansion:
+/// P(F) = P(x) * P(F[x := 1]) + (1 - P(x)) * P(F[x := 0])
+/// on a simplifying expression graph, where identical cofactors are computed only once and subexpressions
+/// over disjoint sets of variables are treated as independent events (i.e P(G && H) = P(G) * P(H)).
+pub fn exact_probability(bexp: &BooleanExpression, probabilities: &[f64]) -> f64 {
+    if bexp.variables().len() <= ENUMERATION_LIMIT {
+        enumerate_probability(bexp, probabilities)
+    } else {
+        shannon_probability(bexp, probabilities)
+    }
+}
+
+/// Computes the probability that the expression is true by summing over its satisfying rows.
+pub fn enumerate_probability(bexp: &BooleanExpression, probabilities: &[f64]) -> f64 {
+    let number_of_vars = bexp.variables().len();
+    Rows::new(bexp, RowOrder::Binary)
+        .with_result(1)
+        .map(|(input, _)| {
+            probabilities
+                .iter()
+                .enumerate()
+                .map(|(id, p)| {
+                    if input.get_bit(number_of_vars - 1 - id) == Some(1) {
+                        *p
+                    } else {
+                        1.0 - *p
+                    }
+                })
+                .product::<f64>()
+        })
+        .sum()
+}
+
+/// Computes the probability that the expression is true with Shannon expansion (see exact_probability).
+pub fn shannon_probability(bexp: &BooleanExpression, probabilities: &[f64]) -> f64 {
+    let mut graph = ExpressionGraph::new();
+    let root = graph.add_expression(bexp);
+    let mut expansion = ShannonExpansion {
+        graph,
+        probabilities,
+        ranges: HashMap::new(),
+        memo: HashMap::new(),
+    };
+    expansion.probability(root)
+}
+
+/// How the probability of a node of the Shannon expansion is computed from other nodes.
+#[derive(Clone, Copy)]
+enum Step {
+    Not(usize),
+    Independent(usize, usize),
+    /// The variable and the cofactors of the node where it is 1 and 0
+    Split(u32, usize, usize),
+}
+
+struct ShannonExpansion<'a> {
+    graph: ExpressionGraph,
+    probabilities: &'a [f64],
+    // The smallest and the largest variable of every node that was split or checked for independence,
+    // None for constants. This is all that is cached about the supports, since caching the supports
+    // themselves takes quadratic memory for long chains of gates.
+    ranges: HashMap<usize, Option<(u32, u32)>>,
+    memo: HashMap<usize, f64>,
+}
+
+impl<'a> ShannonExpansion<'a> {
+    /// Computes the probability of a node from the probabilities of its operands when they are independent,
+    /// or else from the probabilities of its two cofactors on its smallest variable.
+    /// The nodes are visited with an explicit stack, since the graph can be very deep.
+    fn probability(&mut self, root: usize) -> f64 {
+        // A node is pushed again with its step above the nodes that the step needs
+        let mut stack: Vec<(usize, Option<Step>)> = vec![(root, None)];
+        while let Some((node, step)) = stack.pop() {
+            if self.memo.contains_key(&node) {
+                continue;
+            }
+            let step = match step {
+                Some(step) => step,
+                None => {
+                    let step = match self.graph.gate(node) {
+                        Gate::Constant(value) => {
+                            self.memo.insert(node, value as f64);
+                            continue;
+                        }
+                        Gate::Variable(id) => {
+                            self.memo.insert(node, self.probabilities[id as usize]);
+                            continue;
+                        }
+                        Gate::Not(operand) => Step::Not(operand),
+                        Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs)
+                            if self.are_independent(lhs, rhs) =>
+                        {
+                            Step::Independent(lhs, rhs)
+                        }
+                        _ => {
+                            // The graph simplifies constant functions, so every other node has variables
+                            let (id, _) = self.range(node).unwrap();
+                            let high = self.graph.restrict(node, id, 1);
+                            let low = self.graph.restrict(node, id, 0);
+                            Step::Split(id, high, low)
+                        }
+                    };
+                    let needed = match step {
+                        Step::Not(operand) => vec![operand],
+                        Step::Independent(lhs, rhs) | Step::Split(_, lhs, rhs) => vec![lhs, rhs],
+                    };
+                    let missing: Vec<usize> = needed
+                        .into_iter()
+                        .filter(|needed| !self.memo.contains_key(needed))
+                        .collect();
+                    if !missing.is_empty() {
+                        stack.push((node, Some(step)));
+                        stack.extend(missing.into_iter().map(|needed| (needed, None)));
+                        continue;
+                    }
+                    step
+                }
+            };
+            let p = match step {
+                Step::Not(operand) => 1.0 - self.memo[&operand],
+                Step::Independent(lhs, rhs) => {
+                    let (lhs, rhs) = (self.memo[&lhs], self.memo[&rhs]);
+                    match self.graph.gate(node) {
+                        Gate::And(_, _) => lhs * rhs,
+                        Gate::Or(_, _) => 1.0 - (1.0 - lhs) * (1.0 - rhs),
+                        _ => lhs * (1.0 - rhs) + rhs * (1.0 - lhs),
+                    }
+                }
+                Step::Split(id, high, low) => {
+                    let p = self.probabilities[id as usize];
+                    p * self.memo[&high] + (1.0 - p) * self.memo[&low]
+                }
+            };
+            self.memo.insert(node, p);
+        }
+        self.memo[&root]
+    }
+
+    fn range(&mut self, node: usize) -> Option<(u32, u32)> {
+        self.graph.range(node, &mut self.ranges)
+    }
+
+    fn are_independent(&mut self, lhs: usize, rhs: usize) -> bool {
+        let (lhs_range, rhs_range) = match (self.range(lhs), self.range(rhs)) {
+            (Some(lhs_range), Some(rhs_range)) => (lhs_range, rhs_range),
+            _ => return true,
+        };
+        // The variables are numbered in the order they appear, so the operands of a gate
+        // usually have ranges that don't overlap, which is enough
+        if lhs_range.1 < rhs_range.0 || rhs_range.1 < lhs_range.0 {
+            return true;
+        }
+        // Otherwise look for the variables of the operand with the narrower range in the other one,
+        // skipping the nodes whose ranges don't overlap them and stopping at the first common variable
+        let width = |(min, max): (u32, u32)| max - min;
+        let (narrow, wide) = if width(lhs_range) <= width(rhs_range) {
+            (lhs, rhs)
+        } else {
+            (rhs, lhs)
+        };
+        let (min, max) = self.ranges[&narrow].unwrap();
+        let variables: HashSet<u32> = self.graph.support(narrow).into_iter().collect();
+        let mut seen = HashSet::new();
+        let mut stack = vec![wide];
+        while let Some(node) = stack.pop() {
+            match self.ranges[&node] {
+                Some((node_min, node_max)) if node_min <= max && min <= node_max => {}
+                _ => continue,
+            }
+            if !seen.insert(node) {
+                continue;
+            }
+            match self.graph.gate(node) {
+                Gate::Variable(id) if variables.contains(&id) => return false,
+                Gate::Variable(_) | Gate::Constant(_) => {}
+                Gate::Not(operand) => stack.push(operand),
+                Gate::And(lhs, rhs) | Gate::Or(lhs, rhs) | Gate::Xor(lhs, rhs) => {
+                    stack.push(lhs);
+                    stack.push(rhs);
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug)]
+/// An estimate of the probability that an expression is true, together with its confidence interval.
+pub struct Estimate {
+    pub samples: u64,
+    pub satisfying: u64,
+    pub probability: f64,
+    pub confidence: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Estimate {
+    #[inline]
+    /// Gets the largest distance of the estimate from the ends of its confidence interval.
+    pub fn error_bound(&self) -> f64 {
+        (self.probability - self.lower).max(self.upper - self.probability)
+    }
+}
+
+/// Estimates the probability that the expression is true with Monte Carlo sampling.
+/// Every sample assigns each variable independently, setting it to 1 with the probability at its id.
+/// The confidence interval is the Wilson score interval for the given confidence level (i.e 0.95),
+/// which behaves well even when the probability is close to 0 or 1.
+pub fn estimate(
+    bexp: &BooleanExpression,
+    probabilities: &[f64],
+    samples: u64,
+    confidence: f64,
+    random: &mut Random,
+) -> Estimate {
+    let mut values = vec![0u8; probabilities.len()];
+    let mut satisfying = 0;
+    for _ in 0..samples {
+        for (value, probability) in values.iter_mut().zip(probabilities) {
+            *value = (random.next_f64() < *probability) as u8;
+        }
+        satisfying += bexp.evaluate_with(|id| values[id as usize]) as u64;
+    }
+
+    let n = samples as f64;
+    let p = if samples == 0 {
+        0.0
+    } else {
+        satisfying as f64 / n
+    };
+    let z = inverse_normal_cdf(0.5 + confidence / 2.0);
+    let (lower, upper) = if samples == 0 {
+        (0.0, 1.0)
+    } else {
+        let z2 = z * z;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let half_width = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+        (
+            (center - half_width).max(0.0),
+            (center + half_width).min(1.0),
+        )
+    };
+    Estimate {
+        samples,
+        satisfying,
+        probability: p,
+        confidence,
+        lower,
+        upper,
+    }
+}
+
+/// Approximates the quantile function of the standard normal distribution
+/// (Acklam's algorithm, with a relative error of about 1e-9).
+fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const LOW: f64 = 0.02425;
+
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    if p < LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -inverse_normal_cdf(1.0 - p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::test_util::variable_name;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_shannon_probability_matches_enumeration() {
+        let exp = Parser::new(Token::lexer("(A ^ B) && !(C || A) || D && (B || !E) ^ C"))
+            .parse()
+            .unwrap();
+        let probabilities = [0.1, 0.25, 0.5, 0.7, 0.95];
+        let expected = enumerate_probability(&exp, &probabilities);
+        assert!((shannon_probability(&exp, &probabilities) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_exact_probability_of_many_variables() {
+        let clauses: Vec<String> = (0..100)
+            .map(|i| format!("({} || {})", variable_name(2 * i), variable_name(2 * i + 1)))
+            .collect();
+        let exp = clauses.join(" && ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        assert_eq!(exp.variables().len(), 200);
+        let probability = exact_probability(&exp, &[0.5; 200]);
+        assert!((probability / 0.75f64.powi(100) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shannon_probability_of_deep_chain() {
+        // The last variable is also the first one, so the chain is split on it once and the whole
+        // chain is restricted, then its cofactor for 1 is a chain of independent gates
+        let n = 30_000;
+        let names: Vec<String> = (0..n).map(variable_name).collect();
+        let exp = format!(
+            "({} ^ {}) && {} && {}",
+            names[0],
+            names[1],
+            names[2..].join(" && "),
+            names[0]
+        );
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        let mut probabilities = vec![1.0; n];
+        probabilities[0] = 0.5;
+        probabilities[1] = 0.5;
+        assert!((shannon_probability(&exp, &probabilities) - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_inverse_normal_cdf() {
+        assert!((inverse_normal_cdf(0.975) - 1.959_964).abs() < 1e-6);
+        assert!((inverse_normal_cdf(0.5)).abs() < 1e-9);
+        assert!((inverse_normal_cdf(0.005) + 2.575_829).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_estimate_is_within_interval() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        let probabilities = [0.5, 0.5, 0.2];
+        let estimate = estimate(&exp, &probabilities, 20_000, 0.99, &mut Random::new(1));
+        // P(A && B || C) = 1 - (1 - 0.25) * (1 - 0.2)
+        assert!(estimate.lower <= 0.4 && 0.4 <= estimate.upper);
+        assert!(estimate.error_bound() < 0.02);
+    }
+}
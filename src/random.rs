@@ -0,0 +1,45 @@
+use crate::bit_vector::BitVector;
+use crate::bitstring_trait::*;
+
+/// A small pseudo random number generator (SplitMix64).
+/// It is not cryptographically secure but it is fast, has good statistical quality and
+/// generates the same numbers for the same seed, which is all that sampling needs.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a new generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generates the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Generates a random number in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Generates a random bitvector of the given length where every bit is set with probability 1/2.
+    pub fn next_bits(&mut self, len: usize) -> BitVector {
+        let mut bits = BitVector::new(len);
+        let mut word = 0;
+        for pos in 0..len {
+            if pos % 64 == 0 {
+                word = self.next_u64();
+            }
+            if (word >> (pos % 64)) & 1 == 1 {
+                bits.set_bit(pos);
+            }
+        }
+        bits
+    }
+}
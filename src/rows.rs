@@ -0,0 +1,288 @@
+use crate::bit_vector::BitVector;
+use crate::bitstring_trait::*;
+use crate::boolean_expression::*;
+use crate::circuit::Circuit;
+use crate::random::Random;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// The order in which the rows of a truth table are enumerated.
+/// Binary is the order of the numbers that the rows represent.
+/// Gray is the reflected binary Gray code order, where consecutive rows differ in a single variable.
+/// This is also the order of the rows and columns of a Karnaugh map.
+pub enum RowOrder {
+    #[default]
+    Binary,
+    Gray,
+}
+
+/// A lazy iterator over the rows of the truth table of a boolean expression.
+/// Every item is the assignment of the row, indexed the same way as in BooleanExpression::evaluate,
+/// together with the result of the expression for that assignment.
+/// The rows are numbered from 0 to 2^N - 1 in the chosen order and the iterator can be restricted to any
+/// range of them (see starting_at and ending_at). It can also be walked backwards from its end
+/// since it is a DoubleEndedIterator.
+/// Consecutive rows are evaluated incrementally, so only the parts of the expression that depend on the
+/// variables that changed are re-evaluated.
+pub struct Rows {
+    circuit: Circuit,
+    order: RowOrder,
+    front: BitVector,
+    back: BitVector,
+    finished: bool,
+}
+
+impl Rows {
+    /// Creates an iterator over all the rows of the expression's truth table in the given order.
+    pub fn new(bexp: &BooleanExpression, order: RowOrder) -> Self {
+        let number_of_vars = bexp.variables().len();
+        let mut back = BitVector::new(number_of_vars);
+        back.decrement();
+        Self {
+            circuit: Circuit::new(bexp),
+            order,
+            front: BitVector::new(number_of_vars),
+            back,
+            finished: false,
+        }
+    }
+
+    /// Skips the rows before the given row number.
+    /// The row number must have as many bits as the expression has variables.
+    pub fn starting_at(mut self, row: BitVector) -> Self {
+        self.finished = self.finished || row > self.back;
+        self.front = row;
+        self
+    }
+
+    /// Skips the rows after the given row number.
+    /// The row number must have as many bits as the expression has variables.
+    pub fn ending_at(mut self, row: BitVector) -> Self {
+        self.finished = self.finished || row < self.front;
+        self.back = row;
+        self
+    }
+
+    /// Keeps only the rows for which the expression evaluates to the given result.
+    pub fn with_result(self, result: u8) -> impl DoubleEndedIterator<Item = (BitVector, u8)> {
+        self.filter(move |(_, res)| *res == result)
+    }
+
+    /// Evaluates a single row given its number, regardless of the range of the iterator.
+    pub fn get(&mut self, row: BitVector) -> (BitVector, u8) {
+        let input = match self.order {
+            RowOrder::Binary => row,
+            RowOrder::Gray => row.to_gray_code(),
+        };
+        let res = self.circuit.update(&input);
+        (input, res)
+    }
+
+    #[inline]
+    /// Gets the values of the gates of the expression's circuit (see Circuit::gates) for the last row
+    /// that was evaluated, which are the values of all its subexpressions.
+    pub fn gate_values(&self) -> &[u8] {
+        self.circuit.values()
+    }
+}
+
+/// Gets the number of rows of the truth table of an expression with the given number of variables,
+/// or None if it is too big to be counted with a u128.
+pub fn number_of_rows(number_of_vars: usize) -> Option<u128> {
+    if number_of_vars < 128 {
+        Some(1 << number_of_vars)
+    } else {
+        None
+    }
+}
+
+/// Picks count distinct random row numbers of a truth table with the given number of variables
+/// and returns them in ascending order. If the table doesn't have more rows than count, every row is returned.
+pub fn sample(number_of_vars: usize, count: usize, random: &mut Random) -> Vec<BitVector> {
+    if matches!(number_of_rows(number_of_vars), Some(rows) if rows <= count as u128) {
+        let mut row = BitVector::new(number_of_vars);
+        let mut rows = vec![row.clone()];
+        while row.increment() {
+            rows.push(row.clone());
+        }
+        return rows;
+    }
+    let mut rows = HashSet::with_capacity(count);
+    while rows.len() < count {
+        rows.insert(random.next_bits(number_of_vars));
+    }
+    let mut rows: Vec<BitVector> = rows.into_iter().collect();
+    rows.sort();
+    rows
+}
+
+/// Decides which rows of a truth table should be kept.
+/// A row can be required to have a specific result and/or to satisfy a condition, which is another boolean
+/// expression over some of the variables of the table (for example fixing some variables with "A && !C").
+pub struct RowFilter<'a, 'source> {
+    result: Option<u8>,
+    condition: Option<(&'a BooleanExpression<'source>, Vec<usize>)>,
+}
+
+impl<'a, 'source> RowFilter<'a, 'source> {
+    /// Creates a filter that keeps every row.
+    pub fn new() -> Self {
+        Self {
+            result: None,
+            condition: None,
+        }
+    }
+
+    /// Keeps only the rows with the given result.
+    pub fn with_result(mut self, result: u8) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    /// Keeps only the rows for which the condition is true.
+    /// The variables of the condition are matched by name to the variables of the table's expression
+    /// and an error is returned for any variable that the expression doesn't have.
+    pub fn with_condition(
+        mut self,
+        bexp: &BooleanExpression,
+        condition: &'a BooleanExpression<'source>,
+    ) -> Result<Self, EvaluationError> {
+        let number_of_vars = bexp.variables().len();
+        let mut positions = Vec::with_capacity(condition.variables().len());
+        for var in condition.variables() {
+            match bexp.variables().iter().position(|name| name == var) {
+                Some(id) => positions.push(number_of_vars - 1 - id),
+                None => return Err(EvaluationError::UnknownVariable((*var).to_owned())),
+            }
+        }
+        self.condition = Some((condition, positions));
+        Ok(self)
+    }
+
+    /// Checks whether a row is kept. The input is indexed as in BooleanExpression::evaluate.
+    pub fn matches(&self, input: &BitVector, result: u8) -> bool {
+        if matches!(self.result, Some(expected) if expected != result) {
+            return false;
+        }
+        match &self.condition {
+            Some((condition, positions)) => {
+                condition.evaluate_with(|id| input.get_bit(positions[id as usize]).unwrap()) == 1
+            }
+            None => true,
+        }
+    }
+}
+
+impl<'a, 'source> Default for RowFilter<'a, 'source> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Iterator for Rows {
+    type Item = (BitVector, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let row = self.front.clone();
+        if self.front == self.back {
+            self.finished = true;
+        } else {
+            self.front.increment();
+        }
+        Some(self.get(row))
+    }
+}
+
+impl DoubleEndedIterator for Rows {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let row = self.back.clone();
+        if self.front == self.back {
+            self.finished = true;
+        } else {
+            self.back.decrement();
+        }
+        Some(self.get(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_rows_in_both_directions() {
+        let exp = Parser::new(Token::lexer("A && !B")).parse().unwrap();
+        let rows: Vec<u8> = Rows::new(&exp, RowOrder::Binary)
+            .map(|(_, res)| res)
+            .collect();
+        assert_eq!(rows, vec![0, 0, 1, 0]);
+        let rows: Vec<u8> = Rows::new(&exp, RowOrder::Binary)
+            .rev()
+            .map(|(_, res)| res)
+            .collect();
+        assert_eq!(rows, vec![0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_rows_range_in_gray_order() {
+        let exp = Parser::new(Token::lexer("A ^ B ^ C")).parse().unwrap();
+        let rows: Vec<(BitVector, u8)> = Rows::new(&exp, RowOrder::Gray)
+            .starting_at(BitVector::from_u128(3, 2))
+            .ending_at(BitVector::from_u128(3, 4))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (BitVector::from_u128(3, 3), 0),
+                (BitVector::from_u128(3, 2), 1),
+                (BitVector::from_u128(3, 6), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rows_with_result() {
+        let exp = Parser::new(Token::lexer("A || B")).parse().unwrap();
+        let rows: Vec<BitVector> = Rows::new(&exp, RowOrder::Binary)
+            .with_result(0)
+            .map(|(input, _)| input)
+            .collect();
+        assert_eq!(rows, vec![BitVector::new(2)]);
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut random = Random::new(7);
+        let rows = sample(40, 100, &mut random);
+        assert_eq!(rows.len(), 100);
+        assert!(rows.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(sample(3, 10, &mut random).len(), 8);
+    }
+
+    #[test]
+    fn test_row_filter_condition() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        let condition = Parser::new(Token::lexer("!C && A")).parse().unwrap();
+        let filter = RowFilter::new()
+            .with_result(1)
+            .with_condition(&exp, &condition)
+            .unwrap();
+        let rows: Vec<BitVector> = Rows::new(&exp, RowOrder::Binary)
+            .filter(|(input, res)| filter.matches(input, *res))
+            .map(|(input, _)| input)
+            .collect();
+        assert_eq!(rows, vec![BitVector::from_u128(3, 6)]);
+
+        let condition = Parser::new(Token::lexer("D")).parse().unwrap();
+        assert!(RowFilter::new().with_condition(&exp, &condition).is_err());
+    }
+}
@@ -0,0 +1,546 @@
+use crate::boolean_expression::BooleanExpression;
+use crate::cnf::{Cnf, Literal};
+use std::mem;
+
+/// The number of conflicts of the first restart interval. The intervals follow the Luby sequence
+/// (1, 1, 2, 1, 1, 2, 4, ...) multiplied by this.
+const RESTART_INTERVAL: u64 = 100;
+/// The factor by which the activities of the variables decay after every conflict.
+const ACTIVITY_DECAY: f64 = 0.95;
+
+/// A conflict driven clause learning (CDCL) SAT solver.
+/// Every clause watches two of its literals, which are kept in its first two positions, and it is only
+/// visited during propagation when one of them becomes false. When propagation reaches a conflict, the
+/// solver learns a clause that explains it (the first unique implication point), jumps back to the level
+/// where that clause implies a new literal and bumps the activity of the variables involved, so that the
+/// next decisions are made on the variables of the recent conflicts (VSIDS). The search restarts from
+/// scratch in increasing intervals of conflicts, keeping the learned clauses and the last value of every
+/// variable (phase saving).
+/// Clauses can be added between calls of solve, for example to exclude the solutions that were already found.
+pub struct Solver {
+    clauses: Vec<Vec<Literal>>,
+    // The clauses that watch each literal, indexed by Literal::index
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Literal>,
+    // The length of the trail before every decision
+    trail_limits: Vec<usize>,
+    propagated: usize,
+    activities: Vec<f64>,
+    activity_increment: f64,
+    order: VariableOrder,
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    unsatisfiable: bool,
+    conflicts: u64,
+}
+
+impl Solver {
+    /// Creates a solver without clauses over the given number of variables.
+    pub fn new(number_of_vars: usize) -> Self {
+        let mut order = VariableOrder::new(number_of_vars);
+        let activities = vec![0.0; number_of_vars];
+        for var in 0..number_of_vars {
+            order.insert(var as u32, &activities);
+        }
+        Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2 * number_of_vars],
+            values: vec![None; number_of_vars],
+            levels: vec![0; number_of_vars],
+            reasons: vec![None; number_of_vars],
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagated: 0,
+            activities,
+            activity_increment: 1.0,
+            order,
+            phases: vec![false; number_of_vars],
+            seen: vec![false; number_of_vars],
+            unsatisfiable: false,
+            conflicts: 0,
+        }
+    }
+
+    /// Creates a solver with the clauses of a CNF.
+    pub fn from_cnf(cnf: &Cnf) -> Self {
+        let mut solver = Self::new(cnf.number_of_vars);
+        for clause in &cnf.clauses {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+
+    #[inline]
+    pub fn number_of_vars(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Gets the number of conflicts that the solver has gone through so far.
+    #[inline]
+    pub fn conflicts(&self) -> u64 {
+        self.conflicts
+    }
+
+    /// Adds a clause. Returns false if the solver found out that the clauses can't be satisfied.
+    pub fn add_clause(&mut self, literals: &[Literal]) -> bool {
+        if self.unsatisfiable {
+            return false;
+        }
+        let mut clause: Vec<Literal> = Vec::with_capacity(literals.len());
+        for &literal in literals {
+            match self.value(literal) {
+                // Satisfied for good, since clauses are only added at level 0
+                Some(true) => return true,
+                Some(false) => {}
+                None if clause.contains(&!literal) => return true,
+                None if clause.contains(&literal) => {}
+                None => clause.push(literal),
+            }
+        }
+        match clause.len() {
+            0 => self.unsatisfiable = true,
+            1 => {
+                self.assign(clause[0], None);
+                if self.propagate().is_some() {
+                    self.unsatisfiable = true;
+                }
+            }
+            _ => {
+                self.attach(clause);
+            }
+        }
+        !self.unsatisfiable
+    }
+
+    /// Searches for an assignment of the variables, indexed by variable, that satisfies every clause,
+    /// or returns None if there is none.
+    pub fn solve(&mut self) -> Option<Vec<bool>> {
+        if self.unsatisfiable {
+            return None;
+        }
+        let mut restart = 0;
+        loop {
+            restart += 1;
+            match self.search(RESTART_INTERVAL * luby(restart)) {
+                Some(true) => {
+                    let model = self
+                        .values
+                        .iter()
+                        .map(|value| *value == Some(true))
+                        .collect();
+                    self.backtrack(0);
+                    return Some(model);
+                }
+                Some(false) => {
+                    self.unsatisfiable = true;
+                    return None;
+                }
+                None => self.backtrack(0),
+            }
+        }
+    }
+
+    /// Runs the search until it finds a model (Some(true)), proves that there isn't one (Some(false)) or
+    /// reaches the given number of conflicts (None).
+    fn search(&mut self, max_conflicts: u64) -> Option<bool> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                self.conflicts += 1;
+                if self.trail_limits.is_empty() {
+                    return Some(false);
+                }
+                let (learned, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learned.len() == 1 {
+                    self.assign(learned[0], None);
+                } else {
+                    let asserting = learned[0];
+                    let clause = self.attach(learned);
+                    self.assign(asserting, Some(clause));
+                }
+                self.activity_increment /= ACTIVITY_DECAY;
+            } else {
+                if conflicts >= max_conflicts {
+                    return None;
+                }
+                let var = loop {
+                    match self.order.pop(&self.activities) {
+                        Some(var) if self.values[var as usize].is_some() => continue,
+                        Some(var) => break var,
+                        None => return Some(true),
+                    }
+                };
+                self.trail_limits.push(self.trail.len());
+                let negated = !self.phases[var as usize];
+                self.assign(Literal::new(var, negated), None);
+            }
+        }
+    }
+
+    /// Propagates the literals of the trail that haven't been propagated yet and returns
+    /// the clause that became false, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_literal = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watchers = mem::take(&mut self.watches[false_literal.index()]);
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                let values = &self.values;
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_literal {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if literal_value(values, first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let replacement =
+                    (2..clause.len()).find(|k| literal_value(values, clause[*k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if literal_value(values, first) == Some(false) {
+                    conflict = Some(index);
+                    break;
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            watchers.append(&mut self.watches[false_literal.index()]);
+            self.watches[false_literal.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Derives the clause to learn from a conflict by resolving it with the reasons of its literals of the
+    /// current level until a single one is left (the first unique implication point).
+    /// Returns the clause, with the literal that it asserts first and a literal of the level to jump
+    /// back to second, together with that level.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Literal>, usize) {
+        let level = self.trail_limits.len();
+        let mut learned = vec![Literal::positive(0)];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut asserting = None;
+        loop {
+            // The first literal of a reason is the one that it implied
+            let start = if asserting.is_some() { 1 } else { 0 };
+            for k in start..self.clauses[clause].len() {
+                let literal = self.clauses[clause][k];
+                let var = literal.var() as usize;
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == level {
+                    pending += 1;
+                } else {
+                    learned.push(literal);
+                }
+            }
+            let literal = loop {
+                index -= 1;
+                if self.seen[self.trail[index].var() as usize] {
+                    break self.trail[index];
+                }
+            };
+            self.seen[literal.var() as usize] = false;
+            asserting = Some(literal);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            clause = self.reasons[literal.var() as usize].unwrap();
+        }
+        learned[0] = !asserting.unwrap();
+        for literal in &learned[1..] {
+            self.seen[literal.var() as usize] = false;
+        }
+
+        let mut backtrack_level = 0;
+        for k in 1..learned.len() {
+            let level = self.levels[learned[k].var() as usize];
+            if level > backtrack_level {
+                backtrack_level = level;
+                learned.swap(1, k);
+            }
+        }
+        (learned, backtrack_level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.trail_limits.len() <= level {
+            return;
+        }
+        let limit = self.trail_limits[level];
+        for literal in self.trail.drain(limit..) {
+            let var = literal.var() as usize;
+            self.phases[var] = !literal.is_negated();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.order.insert(var as u32, &self.activities);
+        }
+        self.trail_limits.truncate(level);
+        self.propagated = self.trail.len();
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activities[var] += self.activity_increment;
+        if self.activities[var] > 1e100 {
+            for activity in &mut self.activities {
+                *activity *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+        }
+        self.order.increase(var as u32, &self.activities);
+    }
+
+    fn assign(&mut self, literal: Literal, reason: Option<usize>) {
+        let var = literal.var() as usize;
+        self.values[var] = Some(!literal.is_negated());
+        self.levels[var] = self.trail_limits.len();
+        self.reasons[var] = reason;
+        self.trail.push(literal);
+    }
+
+    /// Adds a clause of at least two literals, watching its first two literals.
+    fn attach(&mut self, clause: Vec<Literal>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].index()].push(index);
+        self.watches[clause[1].index()].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    #[inline]
+    fn value(&self, literal: Literal) -> Option<bool> {
+        literal_value(&self.values, literal)
+    }
+}
+
+#[inline]
+fn literal_value(values: &[Option<bool>], literal: Literal) -> Option<bool> {
+    values[literal.var() as usize].map(|value| value != literal.is_negated())
+}
+
+/// Gets the i-th element (starting from 1) of the Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...
+fn luby(mut i: u64) -> u64 {
+    loop {
+        // The smallest k such that i <= 2^k - 1
+        let k = 64 - i.leading_zeros();
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// A binary max-heap of variables ordered by activity that can increase the activity of a variable in place.
+struct VariableOrder {
+    heap: Vec<u32>,
+    positions: Vec<Option<usize>>,
+}
+
+impl VariableOrder {
+    fn new(number_of_vars: usize) -> Self {
+        Self {
+            heap: Vec::with_capacity(number_of_vars),
+            positions: vec![None; number_of_vars],
+        }
+    }
+
+    fn insert(&mut self, var: u32, activities: &[f64]) {
+        if self.positions[var as usize].is_none() {
+            self.heap.push(var);
+            self.positions[var as usize] = Some(self.heap.len() - 1);
+            self.sift_up(self.heap.len() - 1, activities);
+        }
+    }
+
+    fn increase(&mut self, var: u32, activities: &[f64]) {
+        if let Some(position) = self.positions[var as usize] {
+            self.sift_up(position, activities);
+        }
+    }
+
+    fn pop(&mut self, activities: &[f64]) -> Option<u32> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.positions[top as usize] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last as usize] = Some(0);
+            self.sift_down(0, activities);
+        }
+        Some(top)
+    }
+
+    fn sift_up(&mut self, mut position: usize, activities: &[f64]) {
+        let var = self.heap[position];
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if activities[self.heap[parent] as usize] >= activities[var as usize] {
+                break;
+            }
+            self.heap[position] = self.heap[parent];
+            self.positions[self.heap[position] as usize] = Some(position);
+            position = parent;
+        }
+        self.heap[position] = var;
+        self.positions[var as usize] = Some(position);
+    }
+
+    fn sift_down(&mut self, mut position: usize, activities: &[f64]) {
+        let var = self.heap[position];
+        loop {
+            let mut child = 2 * position + 1;
+            if child >= self.heap.len() {
+                break;
+            }
+            if child + 1 < self.heap.len()
+                && activities[self.heap[child + 1] as usize] > activities[self.heap[child] as usize]
+            {
+                child += 1;
+            }
+            if activities[self.heap[child] as usize] <= activities[var as usize] {
+                break;
+            }
+            self.heap[position] = self.heap[child];
+            self.positions[self.heap[position] as usize] = Some(position);
+            position = child;
+        }
+        self.heap[position] = var;
+        self.positions[var as usize] = Some(position);
+    }
+}
+
+/// The result of a search of the SAT solver for a model of a CNF, with the statistics that batt sat prints.
+#[derive(Debug)]
+pub struct Solution {
+    /// The values of the variables, indexed by id, or None if the CNF is unsatisfiable
+    pub model: Option<Vec<u8>>,
+    pub cnf_vars: usize,
+    pub cnf_clauses: usize,
+    pub conflicts: u64,
+}
+
+/// Searches for an assignment of the expression's variables, indexed by id, that makes it true,
+/// by converting it to CNF and running the CDCL solver. Returns None if the expression is unsatisfiable.
+pub fn solve(bexp: &BooleanExpression) -> Option<Vec<u8>> {
+    solve_with_statistics(bexp).model
+}
+
+/// Same as solve, but also returns the size of the CNF and the number of conflicts of the search.
+pub fn solve_with_statistics(bexp: &BooleanExpression) -> Solution {
+    solve_cnf(&Cnf::from_expression(bexp), bexp.variables().len())
+}
+
+/// Runs the CDCL solver on a CNF built from a function of the given number of variables, which are the first
+/// variables of the CNF (the others are the variables of its gates), and keeps only their values in the model.
+pub fn solve_cnf(cnf: &Cnf, number_of_vars: usize) -> Solution {
+    let mut solver = Solver::from_cnf(cnf);
+    let model = solver.solve().map(|model| {
+        model[..number_of_vars]
+            .iter()
+            .map(|value| *value as u8)
+            .collect()
+    });
+    Solution {
+        model,
+        cnf_vars: cnf.number_of_vars,
+        cnf_clauses: cnf.clauses.len(),
+        conflicts: solver.conflicts(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::test_util::variable_name;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_luby() {
+        let sequence: Vec<u64> = (1..=15).map(luby).collect();
+        assert_eq!(sequence, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_solve_expression() {
+        let exp = Parser::new(Token::lexer(
+            "(A || B) && (!A || C) && (!B || !C) && (A ^ B)",
+        ))
+        .parse()
+        .unwrap();
+        let model = solve(&exp).unwrap();
+        assert_eq!(exp.evaluate_with(|id| model[id as usize]), 1);
+        let exp = Parser::new(Token::lexer(
+            "(A || B) && (!A || B) && (A || !B) && (!A || !B)",
+        ))
+        .parse()
+        .unwrap();
+        assert_eq!(solve(&exp), None);
+    }
+
+    #[test]
+    fn test_pigeonhole_is_unsatisfiable() {
+        // 6 pigeons in 5 holes, where var 5 * p + h means that pigeon p is in hole h
+        let (pigeons, holes) = (6u32, 5u32);
+        let mut solver = Solver::new((pigeons * holes) as usize);
+        for p in 0..pigeons {
+            let clause: Vec<Literal> = (0..holes)
+                .map(|h| Literal::positive(holes * p + h))
+                .collect();
+            solver.add_clause(&clause);
+        }
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    solver.add_clause(&[
+                        Literal::negative(holes * p + h),
+                        Literal::negative(holes * q + h),
+                    ]);
+                }
+            }
+        }
+        assert_eq!(solver.solve(), None);
+    }
+
+    #[test]
+    fn test_many_variables() {
+        // A chain of implications over 300 variables with both ends fixed
+        let names: Vec<String> = (0..300).map(variable_name).collect();
+        let mut clauses: Vec<String> = names
+            .windows(2)
+            .map(|pair| format!("(!{} || {})", pair[0], pair[1]))
+            .collect();
+        clauses.push(names[0].clone());
+        let exp = clauses.join(" && ");
+        let bexp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        let model = solve(&bexp).unwrap();
+        assert!(model.iter().all(|value| *value == 1));
+
+        let exp = format!("{} && !{}", exp, names[299]);
+        let bexp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        assert_eq!(solve(&bexp), None);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::bdd::BddManager;
+use crate::bit_vector::BitVector;
+use crate::bitstring_trait::*;
+use crate::boolean_expression::{BooleanExpression, EvaluationError};
+use crate::circuit::Circuit;
+use crate::expression_graph::ExpressionGraph;
+
+/// Computes the boolean difference (derivative) of an expression with respect to a variable:
+/// dF/dx = F[x := 0] ^ F[x := 1]
+/// which is true exactly for the assignments of the other variables where the value of x matters.
+/// The result is simplified and is over the variables that are left (see ExpressionGraph::to_expression),
+/// which returns an error if it is too large to write out.
+pub fn derivative<'source>(
+    bexp: &BooleanExpression<'source>,
+    id: u32,
+) -> Result<BooleanExpression<'source>, EvaluationError> {
+    let mut graph = ExpressionGraph::new();
+    let root = graph.add_expression(bexp);
+    let low = graph.restrict(root, id, 0);
+    let high = graph.restrict(root, id, 1);
+    let difference = graph.xor(low, high);
+    graph.to_expression(difference, bexp.variables())
+}
+
+/// Computes the influence of every variable, indexed by id, which is the fraction of the rows
+/// of the truth table where flipping the variable flips the result.
+/// The influences are computed on the BDD of the expression in a single pass (see BddManager::influences),
+/// so it doesn't need to enumerate the truth table or build a derivative for every variable.
+/// For monotone functions the influence is also known as the Banzhaf value of the variable.
+pub fn influences(bexp: &BooleanExpression) -> Vec<f64> {
+    let (mut manager, root) = BddManager::from_expression(bexp);
+    manager.influences(root)
+}
+
+#[derive(Debug)]
+/// The measures of how much each variable matters that need the whole truth table.
+pub struct Sensitivity {
+    /// The Shapley value of every variable, indexed by id. This is the average change of the result
+    /// when the variable is set to 1 after the variables before it in a random order of all the variables
+    /// have been set to 1 (and the rest are 0). For monotone functions the values add up to F(1, .., 1) - F(0, .., 0).
+    pub shapley: Vec<f64>,
+    /// The largest number of variables that flip the result when flipped alone, over all rows.
+    pub max_sensitivity: usize,
+}
+
+/// Computes the Shapley values and the maximum sensitivity of an expression by enumerating its truth table.
+/// This takes 2^N steps, so it is only practical for expressions with a few variables.
+pub fn enumerate_sensitivity(bexp: &BooleanExpression) -> Sensitivity {
+    let number_of_vars = bexp.variables().len();
+    let mut circuit = Circuit::new(bexp);
+    let mut input = BitVector::new(number_of_vars);
+    let mut shapley = vec![0.0; number_of_vars];
+    let mut max_sensitivity = 0;
+    // The weight of a row for the Shapley value of a variable that is 0 in it and
+    // has k other variables set: k! (n - k - 1)! / n! = 1 / (n * C(n - 1, k))
+    let weights: Vec<f64> = (0..number_of_vars)
+        .map(|k| 1.0 / (number_of_vars as f64 * binomial(number_of_vars - 1, k)))
+        .collect();
+    loop {
+        let res = circuit.update(&input);
+        let ones = input.count_ones();
+        let mut sensitivity = 0;
+        for (id, value) in shapley.iter_mut().enumerate() {
+            let flipped = circuit.toggle(id as u32);
+            circuit.toggle(id as u32);
+            if flipped != res {
+                sensitivity += 1;
+            }
+            let pos = number_of_vars - 1 - id;
+            if input.get_bit(pos) == Some(0) {
+                *value += weights[ones] * (flipped as f64 - res as f64);
+            }
+        }
+        max_sensitivity = max_sensitivity.max(sensitivity);
+        if !input.increment() {
+            break;
+        }
+    }
+    Sensitivity {
+        shapley,
+        max_sensitivity,
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |res, i| res * (n - i) as f64 / (i + 1) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counting::count_models;
+    use crate::parser::Parser;
+    use crate::test_util::variable_name;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_derivative() {
+        let exp = Parser::new(Token::lexer("A && B || C")).parse().unwrap();
+        assert_eq!(derivative(&exp, 0).unwrap().to_string(), "C ^ B || C");
+        let exp = Parser::new(Token::lexer("A ^ B")).parse().unwrap();
+        assert_eq!(derivative(&exp, 0).unwrap().to_string(), "1");
+    }
+
+    #[test]
+    fn test_influences_and_shapley_values() {
+        // The majority of three variables
+        let exp = Parser::new(Token::lexer("A && B || A && C || B && C"))
+            .parse()
+            .unwrap();
+        for influence in influences(&exp) {
+            assert!((influence - 0.5).abs() < 1e-12);
+        }
+        let sensitivity = enumerate_sensitivity(&exp);
+        for value in sensitivity.shapley {
+            assert!((value - 1.0 / 3.0).abs() < 1e-12);
+        }
+        assert_eq!(sensitivity.max_sensitivity, 2);
+    }
+
+    #[test]
+    fn test_influences_match_derivatives() {
+        let exp = Parser::new(Token::lexer("(A ^ B) && !(C || A) || D && (B || !E) ^ C"))
+            .parse()
+            .unwrap();
+        for (id, influence) in influences(&exp).into_iter().enumerate() {
+            let derivative = derivative(&exp, id as u32).unwrap();
+            let expected = count_models(&derivative).div_power_of_two(derivative.variables().len());
+            assert!((influence - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_influences_of_long_chain() {
+        // Flipping a variable flips its term exactly when the other variable of the term is true
+        let terms: Vec<String> = (0..10_000)
+            .map(|i| format!("({} && {})", variable_name(2 * i), variable_name(2 * i + 1)))
+            .collect();
+        let exp = terms.join(" ^ ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        for influence in influences(&exp) {
+            assert!((influence - 0.5).abs() < 1e-12);
+        }
+    }
+}
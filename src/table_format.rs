@@ -1,23 +1,91 @@
+use crate::bit_vector::BitVector;
 use crate::bitstring_trait::*;
 use crate::boolean_expression::BooleanExpression;
+use crate::circuit::{Circuit, Gate};
+use crate::logic::Ternary;
 
 /// A helper struct that prints the truth table for a given boolean expression
 pub struct TableFormat {
     header: String,
     row_separator: String,
+    variable_widths: Vec<usize>,
     expression_length: usize,
+    // The circuit gate and the column width of every intermediate subexpression column
+    subexpressions: Vec<(usize, usize)>,
 }
 
 impl TableFormat {
     pub fn new(exp: &str, bexp: &BooleanExpression) -> TableFormat {
         let variables = bexp.variables();
-        let header = variables.join("|");
-        let header = format!("|{}|{}|", header, exp);
+        let header = if variables.is_empty() {
+            format!("|{}|", exp)
+        } else {
+            format!("|{}|{}|", variables.join("|"), exp)
+        };
         let row_separator = format!("{:-<1$}", "", header.len());
         TableFormat {
             header,
             row_separator,
+            variable_widths: variables.iter().map(|var| var.len()).collect(),
             expression_length: exp.len(),
+            subexpressions: Vec::new(),
+        }
+    }
+
+    /// Creates a table where every column is at least as wide as the given width,
+    /// for values that take more than one character.
+    pub fn with_column_width(exp: &str, bexp: &BooleanExpression, width: usize) -> TableFormat {
+        let mut variable_widths = Vec::new();
+        let mut header = String::from("|");
+        for var in bexp.variables() {
+            variable_widths.push(var.len().max(width));
+            header.push_str(&format!("{: >1$}|", var, width));
+        }
+        header.push_str(&format!("{: >1$}|", exp, width));
+        let row_separator = format!("{:-<1$}", "", header.len());
+        TableFormat {
+            header,
+            row_separator,
+            variable_widths,
+            expression_length: exp.len().max(width),
+            subexpressions: Vec::new(),
+        }
+    }
+
+    /// Creates a table that, besides the variables and the whole expression, has one column for every
+    /// distinct subexpression in the order that they are evaluated.
+    /// For example the expression "A && !B || C" gets the columns "!B" and "A && !B".
+    /// The values of the columns are the values of the gates of the expression's circuit (see Circuit::gates),
+    /// which Rows already computes for every row (see Rows::gate_values).
+    pub fn with_subexpressions(exp: &str, bexp: &BooleanExpression) -> TableFormat {
+        let circuit = Circuit::new(bexp);
+        let texts = circuit.render(bexp.variables());
+        let root = circuit.gates().len() - 1;
+        let mut columns = bexp
+            .variables()
+            .iter()
+            .map(|var| var.to_string())
+            .collect::<Vec<_>>();
+        let mut subexpressions = Vec::new();
+        for (i, gate) in circuit.gates().iter().enumerate() {
+            match gate {
+                Gate::Variable(_) | Gate::Constant(_) => {}
+                _ if i == root => {}
+                _ => {
+                    subexpressions.push((i, texts[i].len()));
+                    columns.push(texts[i].clone());
+                }
+            }
+        }
+        columns.push(exp.to_owned());
+        let header = format!("|{}|", columns.join("|"));
+        let row_separator = format!("{:-<1$}", "", header.len());
+        TableFormat {
+            header,
+            row_separator,
+            variable_widths: bexp.variables().iter().map(|var| var.len()).collect(),
+            expression_length: exp.len(),
+            subexpressions,
         }
     }
 
@@ -35,19 +103,55 @@ impl TableFormat {
     }
 
     #[inline]
-    pub fn print_evaluation<T>(&self, bexp: &BooleanExpression, input: T, eval_result: u8)
+    /// Prints a row of the truth table. The gate values are only used by the subexpression columns.
+    pub fn print_evaluation(
+        &self,
+        bexp: &BooleanExpression,
+        input: &BitVector,
+        gate_values: &[u8],
+        eval_result: u8,
+    ) {
+        self.print_variables(bexp, input);
+        for (gate, width) in &self.subexpressions {
+            print!("|{: >1$}", gate_values[*gate], width);
+        }
+        println!("|{: >1$}|", eval_result, self.expression_length);
+    }
+
+    #[inline]
+    /// Prints a row of a three-valued truth table. The values are indexed by variable id.
+    pub fn print_ternary_evaluation(&self, values: &[Ternary], eval_result: Ternary) {
+        for (width, value) in self.variable_widths.iter().zip(values) {
+            print!("|{: >1$}", value, width);
+        }
+        println!("|{: >1$}|", eval_result, self.expression_length);
+    }
+
+    #[inline]
+    /// Prints a row of a fuzzy truth table with the given number of decimal digits.
+    /// The values are indexed by variable id.
+    pub fn print_fuzzy_evaluation(&self, values: &[f64], eval_result: f64, precision: usize) {
+        for (width, value) in self.variable_widths.iter().zip(values) {
+            print!("|{: >1$.2$}", value, width, precision);
+        }
+        println!(
+            "|{: >1$.2$}|",
+            eval_result, self.expression_length, precision
+        );
+    }
+
+    #[inline]
+    fn print_variables<T>(&self, bexp: &BooleanExpression, input: &T)
     where
-        T: BitString,
+        T: BitString + ?Sized,
     {
-        let variables = bexp.variables();
-        let number_of_vars = variables.len();
-        for (i, var) in variables.iter().enumerate() {
+        let number_of_vars = bexp.variables().len();
+        for (i, width) in self.variable_widths.iter().enumerate() {
             print!(
                 "|{: >1$}",
-                input.get_bit(number_of_vars - 1 - i as usize).unwrap(),
-                var.len()
+                input.get_bit(number_of_vars - 1 - i).unwrap(),
+                width
             );
         }
-        println!("|{: >1$}|", eval_result, self.expression_length);
     }
 }
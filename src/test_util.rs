@@ -0,0 +1,9 @@
+/// Names the variable number i in the tests that need many variables. Identifiers can only have letters,
+/// so the digits of the number are mapped to letters, i.e variable 12 is "Xbc".
+pub(crate) fn variable_name(i: usize) -> String {
+    let digits = i.to_string();
+    let letters = digits
+        .chars()
+        .map(|c| (b'a' + c.to_digit(10).unwrap() as u8) as char);
+    std::iter::once('X').chain(letters).collect()
+}
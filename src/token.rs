@@ -1,5 +1,6 @@
 use logos::Logos;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Logos, Debug, PartialEq, PartialOrd, Clone, Copy)]
 /// The token of our minimal boolean algrebra expression language
 /// IDENT token is an identifier (a boolean variable) and can be anything
@@ -31,9 +32,6 @@ impl Token {
     /// The binary operators are:
     /// AND, OR and XOR
     pub fn is_binary_operator(self) -> bool {
-        match self {
-            Token::AND | Token::OR | Token::XOR => true,
-            _ => false,
-        }
+        matches!(self, Token::AND | Token::OR | Token::XOR)
     }
 }
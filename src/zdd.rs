@@ -0,0 +1,436 @@
+use crate::bdd::{Bdd, BddManager};
+use crate::big_uint::BigUint;
+use crate::boolean_expression::BooleanExpression;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A family of sets of variables in a ZddManager, identified by its top node.
+/// As with Bdd, two families of the same manager are equal exactly when their handles are equal.
+pub struct Zdd(usize);
+
+impl Zdd {
+    /// The family without any sets.
+    pub const EMPTY: Zdd = Zdd(0);
+    /// The family whose only set is the empty set.
+    pub const BASE: Zdd = Zdd(1);
+
+    #[inline]
+    pub fn is_constant(self) -> bool {
+        self.0 < 2
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ZddNode {
+    var: u32,
+    low: Zdd,
+    high: Zdd,
+}
+
+/// The variable of the two terminal nodes, which are below every variable.
+const TERMINAL: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operation {
+    Union,
+    Intersection,
+    Difference,
+    Join,
+}
+
+/// A manager of zero-suppressed decision diagrams (ZDD), which represent families of sets of variables.
+/// A node splits the family to the sets without its variable (low) and the sets with it (high), and the
+/// nodes whose high child is the empty family are removed, so a variable that is missing from a path
+/// is absent from the sets of that path (instead of being a don't care as in a BDD).
+/// This makes the diagrams of sparse families, where every set has few of the variables, much smaller
+/// than the BDDs of the same functions. The variables are tested in the order of their ids and nodes are
+/// only created through the unique table, so the diagram of every family is canonical.
+pub struct ZddManager {
+    nodes: Vec<ZddNode>,
+    unique: HashMap<ZddNode, Zdd>,
+    computed: HashMap<(Operation, Zdd, Zdd), Zdd>,
+    number_of_vars: usize,
+}
+
+impl ZddManager {
+    /// Creates a manager for families of sets of the given number of variables.
+    pub fn new(number_of_vars: usize) -> Self {
+        let terminal = |value: usize| ZddNode {
+            var: TERMINAL,
+            low: Zdd(value),
+            high: Zdd(value),
+        };
+        Self {
+            nodes: vec![terminal(0), terminal(1)],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+            number_of_vars,
+        }
+    }
+
+    /// Creates a manager over the variables of an expression and builds the family of its models in it.
+    pub fn from_expression(bexp: &BooleanExpression) -> (Self, Zdd) {
+        let mut manager = Self::new(bexp.variables().len());
+        let root = manager.add_expression(bexp);
+        (manager, root)
+    }
+
+    /// Builds the family of the models of an expression, where every model is the set of the variables
+    /// that are true in it. The variable ids of the expression must be variables of the manager.
+    /// The expression is built as a BDD first, which is then converted node by node.
+    pub fn add_expression(&mut self, bexp: &BooleanExpression) -> Zdd {
+        let mut bdd_manager = BddManager::new(self.number_of_vars);
+        let f = bdd_manager.add_expression(bexp);
+        self.add_bdd(&bdd_manager, f)
+    }
+
+    /// Converts a function of a BDD manager that tests the variables in the order of their ids to the
+    /// family of its models.
+    /// Every call converts a function restricted to the variables from var on. The calls go as deep as the
+    /// number of variables, so they run on an explicit stack where every call is visited once to split it
+    /// and once more to combine the results of its children.
+    fn add_bdd(&mut self, bdd_manager: &BddManager, f: Bdd) -> Zdd {
+        let mut memo: HashMap<(Bdd, u32), Zdd> = HashMap::new();
+        let mut calls = vec![(f, 0, false)];
+        let mut results = Vec::new();
+        while let Some((f, var, split)) = calls.pop() {
+            let tests_var = bdd_manager.top_var(f) == Some(var);
+            if split {
+                let res = if tests_var {
+                    let high = results.pop().unwrap();
+                    let low = results.pop().unwrap();
+                    self.make(var, low, high)
+                } else {
+                    // The function doesn't depend on the variable, so the sets come with and without it
+                    let rest = results.pop().unwrap();
+                    self.make(var, rest, rest)
+                };
+                memo.insert((f, var), res);
+                results.push(res);
+            } else if f == Bdd::FALSE {
+                results.push(Zdd::EMPTY);
+            } else if var as usize == self.number_of_vars {
+                results.push(Zdd::BASE);
+            } else if let Some(res) = memo.get(&(f, var)) {
+                results.push(*res);
+            } else {
+                calls.push((f, var, true));
+                if tests_var {
+                    let (low, high) = bdd_manager.children(f);
+                    calls.push((high, var + 1, false));
+                    calls.push((low, var + 1, false));
+                } else {
+                    calls.push((f, var + 1, false));
+                }
+            }
+        }
+        results.pop().unwrap()
+    }
+
+    #[inline]
+    pub fn number_of_vars(&self) -> usize {
+        self.number_of_vars
+    }
+
+    /// Gets the family whose only set is {var}.
+    pub fn singleton(&mut self, var: u32) -> Zdd {
+        self.make(var, Zdd::EMPTY, Zdd::BASE)
+    }
+
+    /// Gets the family of the sets that are in P or in Q.
+    pub fn union(&mut self, p: Zdd, q: Zdd) -> Zdd {
+        self.apply(Operation::Union, p, q)
+    }
+
+    /// Gets the family of the sets that are in both P and Q.
+    pub fn intersection(&mut self, p: Zdd, q: Zdd) -> Zdd {
+        self.apply(Operation::Intersection, p, q)
+    }
+
+    /// Gets the family of the sets of P that are not in Q.
+    pub fn difference(&mut self, p: Zdd, q: Zdd) -> Zdd {
+        self.apply(Operation::Difference, p, q)
+    }
+
+    /// Gets the family of the unions of a set of P with a set of Q.
+    pub fn join(&mut self, p: Zdd, q: Zdd) -> Zdd {
+        self.apply(Operation::Join, p, q)
+    }
+
+    /// Applies a family operation to P and Q. The calls on the cofactors of the operands go as deep as the
+    /// number of variables, so they run on an explicit stack where every call is visited once to split it
+    /// on the top variable and once more to combine the results of its calls, which are on the results stack.
+    fn apply(&mut self, op: Operation, p: Zdd, q: Zdd) -> Zdd {
+        let mut calls = vec![(p, q, false)];
+        let mut results = Vec::new();
+        while let Some((p, q, split)) = calls.pop() {
+            if !split {
+                if let Some(res) = self.terminal_case(op, p, q) {
+                    results.push(res);
+                    continue;
+                }
+            }
+            let (p, q) = match op {
+                Operation::Union | Operation::Intersection => (p.min(q), p.max(q)),
+                // Make P the operand with the top variable, which doesn't change the result
+                Operation::Join if self.nodes[p.0].var > self.nodes[q.0].var => (q, p),
+                _ => (p, q),
+            };
+            let (p_node, q_node) = (self.nodes[p.0], self.nodes[q.0]);
+            let order = p_node.var.cmp(&q_node.var);
+            if !split {
+                if let Some(res) = self.computed.get(&(op, p, q)) {
+                    results.push(*res);
+                    continue;
+                }
+                calls.push((p, q, true));
+                let (p_low, p_high, q_low, q_high) =
+                    (p_node.low, p_node.high, q_node.low, q_node.high);
+                let cofactors = match (op, order) {
+                    (Operation::Join, Ordering::Less) => vec![(p_low, q), (p_high, q)],
+                    // The sets with the variable come from a set with it on either side or on both
+                    (Operation::Join, _) => vec![
+                        (p_low, q_low),
+                        (p_high, q_high),
+                        (p_high, q_low),
+                        (p_low, q_high),
+                    ],
+                    (_, Ordering::Less) => vec![(p_low, q)],
+                    (_, Ordering::Greater) => vec![(p, q_low)],
+                    (_, Ordering::Equal) => vec![(p_low, q_low), (p_high, q_high)],
+                };
+                calls.extend(cofactors.into_iter().rev().map(|(p, q)| (p, q, false)));
+                continue;
+            }
+            let res = match (op, order) {
+                (Operation::Join, Ordering::Equal) => {
+                    let only_q = results.pop().unwrap();
+                    let only_p = results.pop().unwrap();
+                    let both = results.pop().unwrap();
+                    let low = results.pop().unwrap();
+                    let high = self.union(both, only_p);
+                    let high = self.union(high, only_q);
+                    self.make(p_node.var, low, high)
+                }
+                (Operation::Join, _) | (_, Ordering::Equal) => {
+                    let high = results.pop().unwrap();
+                    let low = results.pop().unwrap();
+                    self.make(p_node.var, low, high)
+                }
+                // The sets of P with its top variable are not in Q
+                (Operation::Intersection, _) | (Operation::Difference, Ordering::Greater) => {
+                    results.pop().unwrap()
+                }
+                (_, Ordering::Less) => {
+                    let low = results.pop().unwrap();
+                    self.make(p_node.var, low, p_node.high)
+                }
+                (_, Ordering::Greater) => {
+                    let low = results.pop().unwrap();
+                    self.make(q_node.var, low, q_node.high)
+                }
+            };
+            self.computed.insert((op, p, q), res);
+            results.push(res);
+        }
+        results.pop().unwrap()
+    }
+
+    /// Gets the result of an operation that doesn't need to split its operands.
+    fn terminal_case(&self, op: Operation, p: Zdd, q: Zdd) -> Option<Zdd> {
+        match (op, p, q) {
+            (Operation::Union, Zdd::EMPTY, _) => Some(q),
+            (Operation::Union, _, Zdd::EMPTY) => Some(p),
+            (Operation::Union, _, _) | (Operation::Intersection, _, _) if p == q => Some(p),
+            (Operation::Intersection, Zdd::EMPTY, _) | (Operation::Intersection, _, Zdd::EMPTY) => {
+                Some(Zdd::EMPTY)
+            }
+            (Operation::Difference, Zdd::EMPTY, _) => Some(Zdd::EMPTY),
+            (Operation::Difference, _, Zdd::EMPTY) => Some(p),
+            (Operation::Difference, _, _) if p == q => Some(Zdd::EMPTY),
+            (Operation::Join, Zdd::EMPTY, _) | (Operation::Join, _, Zdd::EMPTY) => Some(Zdd::EMPTY),
+            (Operation::Join, Zdd::BASE, _) => Some(q),
+            (Operation::Join, _, Zdd::BASE) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// Counts the sets of a family.
+    pub fn count(&self, p: Zdd) -> BigUint {
+        // The counts of the nodes are computed on an explicit stack after the ones of their children
+        let mut memo: HashMap<Zdd, BigUint> = HashMap::new();
+        let sets = |memo: &HashMap<Zdd, BigUint>, p: Zdd| {
+            if p.is_constant() {
+                BigUint::from(p.0 as u64)
+            } else {
+                memo[&p].clone()
+            }
+        };
+        let mut stack = vec![(p, false)];
+        while let Some((q, split)) = stack.pop() {
+            if q.is_constant() || memo.contains_key(&q) {
+                continue;
+            }
+            let node = self.nodes[q.0];
+            if split {
+                let count = &sets(&memo, node.low) + &sets(&memo, node.high);
+                memo.insert(q, count);
+            } else {
+                stack.push((q, true));
+                stack.push((node.high, false));
+                stack.push((node.low, false));
+            }
+        }
+        sets(&memo, p)
+    }
+
+    /// Gets a lazy iterator over the sets of a family.
+    pub fn sets(&self, p: Zdd) -> Sets<'_> {
+        Sets {
+            manager: self,
+            stack: vec![(p, Vec::new())],
+        }
+    }
+
+    /// Counts the nodes of the diagram of a family, including the terminals that it reaches.
+    pub fn node_count(&self, p: Zdd) -> usize {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut stack = vec![p];
+        let mut count = 0;
+        while let Some(p) = stack.pop() {
+            if seen[p.0] {
+                continue;
+            }
+            seen[p.0] = true;
+            count += 1;
+            if !p.is_constant() {
+                let node = self.nodes[p.0];
+                stack.push(node.low);
+                stack.push(node.high);
+            }
+        }
+        count
+    }
+
+    /// Gets the node that tests var with the given children, creating it if it doesn't exist.
+    /// The node is suppressed if no set of the family has the variable.
+    fn make(&mut self, var: u32, low: Zdd, high: Zdd) -> Zdd {
+        if high == Zdd::EMPTY {
+            return low;
+        }
+        let node = ZddNode { var, low, high };
+        let nodes = &mut self.nodes;
+        *self.unique.entry(node).or_insert_with(|| {
+            nodes.push(node);
+            Zdd(nodes.len() - 1)
+        })
+    }
+}
+
+/// A lazy iterator over the sets of a family of a ZddManager.
+/// Every set is given as the sorted ids of its variables and the sets are visited depth first,
+/// so the family is never expanded as a whole.
+pub struct Sets<'manager> {
+    manager: &'manager ZddManager,
+    // The nodes that are left to visit with the variables of the path to them
+    stack: Vec<(Zdd, Vec<u32>)>,
+}
+
+impl Iterator for Sets<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((p, set)) = self.stack.pop() {
+            match p {
+                Zdd::EMPTY => {}
+                Zdd::BASE => return Some(set),
+                _ => {
+                    let node = self.manager.nodes[p.0];
+                    let mut with_var = set.clone();
+                    with_var.push(node.var);
+                    self.stack.push((node.high, with_var));
+                    self.stack.push((node.low, set));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::test_util::variable_name;
+    use crate::token::Token;
+    use logos::Logos;
+
+    #[test]
+    fn test_models_as_sets() {
+        let exp = Parser::new(Token::lexer("A && !B || C")).parse().unwrap();
+        let (manager, p) = ZddManager::from_expression(&exp);
+        assert_eq!(manager.count(p), BigUint::from(5));
+        let mut sets: Vec<Vec<u32>> = manager.sets(p).collect();
+        sets.sort();
+        assert_eq!(
+            sets,
+            vec![vec![0], vec![0, 1, 2], vec![0, 2], vec![1, 2], vec![2]]
+        );
+    }
+
+    #[test]
+    fn test_deep_family() {
+        // A chain of implications over 30000 variables, whose models are the prefixes of the variables
+        let names: Vec<String> = (0..30_000).map(variable_name).collect();
+        let clauses: Vec<String> = names
+            .windows(2)
+            .map(|pair| format!("({} || !{})", pair[0], pair[1]))
+            .collect();
+        let exp = clauses.join(" && ");
+        let exp = Parser::new(Token::lexer(&exp)).parse().unwrap();
+        let (mut manager, p) = ZddManager::from_expression(&exp);
+        assert_eq!(manager.count(p), BigUint::from(names.len() as u64 + 1));
+        assert_eq!(manager.node_count(p), names.len() + 1);
+        // The family whose only set has all the variables, which is the longest prefix
+        let mut all = Zdd::BASE;
+        for var in (0..names.len() as u32).rev() {
+            let singleton = manager.singleton(var);
+            all = manager.join(singleton, all);
+        }
+        assert_eq!(manager.intersection(p, all), all);
+        let shorter = manager.difference(p, all);
+        assert_eq!(manager.count(shorter), BigUint::from(names.len() as u64));
+        assert_eq!(manager.union(shorter, all), p);
+        assert_eq!(manager.join(p, all), all);
+    }
+
+    #[test]
+    fn test_family_operations() {
+        let mut manager = ZddManager::new(3);
+        let (a, b, c) = (
+            manager.singleton(0),
+            manager.singleton(1),
+            manager.singleton(2),
+        );
+        // {{A}, {B}} and {{B}, {C}}
+        let p = manager.union(a, b);
+        let q = manager.union(b, c);
+        assert_eq!(manager.intersection(p, q), b);
+        assert_eq!(manager.difference(p, q), a);
+        let pq = manager.join(p, q);
+        let mut sets: Vec<Vec<u32>> = manager.sets(pq).collect();
+        sets.sort();
+        assert_eq!(sets, vec![vec![0, 1], vec![0, 2], vec![1], vec![1, 2]]);
+        // Exactly one of the three variables is a sparse family with a small diagram
+        let exp = Parser::new(Token::lexer(
+            "(A || B || C) && !(A && B) && !(A && C) && !(B && C)",
+        ))
+        .parse()
+        .unwrap();
+        let one_hot = manager.add_expression(&exp);
+        let abc = manager.union(p, c);
+        assert_eq!(one_hot, abc);
+        assert_eq!(manager.node_count(one_hot), 5);
+    }
+}